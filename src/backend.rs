@@ -1,17 +1,24 @@
+use crate::config::{BackendCommandConfig, Config};
 use crate::error::BackendError;
 use crate::framing::{LspFrameReader, LspFrameWriter};
 use crate::message::{RpcId, RpcMessage};
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::process::{Child, ChildStdin, ChildStdout, Command};
 
 /// Supported LSP backend types for Python type checking.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
 pub enum BackendKind {
     Pyright,
     Ty,
     Pyrefly,
+    /// Any stdio LSP server; its command comes from `[backends.custom]`
+    Custom,
 }
 
 impl BackendKind {
@@ -21,14 +28,17 @@ impl BackendKind {
             Self::Pyright => "pyright",
             Self::Ty => "ty",
             Self::Pyrefly => "pyrefly",
+            Self::Custom => "custom",
         }
     }
 
-    fn command(&self) -> &'static str {
+    /// Built-in executable (None for `Custom`, which must be configured)
+    fn command(&self) -> Option<&'static str> {
         match self {
-            Self::Pyright => "pyright-langserver",
-            Self::Ty => "ty",
-            Self::Pyrefly => "pyrefly",
+            Self::Pyright => Some("pyright-langserver"),
+            Self::Ty => Some("ty"),
+            Self::Pyrefly => Some("pyrefly"),
+            Self::Custom => None,
         }
     }
 
@@ -37,15 +47,20 @@ impl BackendKind {
             Self::Pyright => &["--stdio"],
             Self::Ty => &["server"],
             Self::Pyrefly => &["lsp"],
+            Self::Custom => &[],
         }
     }
 
     /// Apply backend-specific environment variables to the command.
-    /// Currently all backends use VIRTUAL_ENV + PATH, but this method
-    /// provides the extension point for future backend-specific env setup.
+    /// Currently all backends use VIRTUAL_ENV + PATH (plus CONDA_PREFIX for
+    /// conda envs), but this method provides the extension point for future
+    /// backend-specific env setup.
     pub fn apply_env(&self, cmd: &mut Command, venv: &Path) {
         let venv_str = venv.to_string_lossy();
         cmd.env("VIRTUAL_ENV", venv_str.as_ref());
+        if crate::venv::is_conda_env(venv) {
+            cmd.env("CONDA_PREFIX", venv_str.as_ref());
+        }
 
         let current_path = std::env::var("PATH").unwrap_or_default();
         let new_path = format!("{}/bin:{}", venv_str, current_path);
@@ -59,6 +74,220 @@ impl std::fmt::Display for BackendKind {
     }
 }
 
+/// Command line used to spawn a backend: the kind's built-in default,
+/// with `[backends.<kind>]` overrides from `.typemux.toml` applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Extra environment variables, applied after the venv environment
+    pub env: Vec<(String, String)>,
+    /// Directory searched after the venv's `bin/` and before PATH
+    pub tool_dir: Option<PathBuf>,
+    /// Fail instead of falling back when the venv has no copy of the executable
+    pub require_venv_local: bool,
+}
+
+/// Where a backend executable was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutableSource {
+    /// `program` is a path (configured explicitly)
+    Explicit,
+    /// The target venv's `bin/`
+    Venv,
+    /// The configured `backend_tool_dir`
+    ToolDir,
+    /// The proxy's own PATH
+    Path,
+}
+
+impl ExecutableSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Explicit => "explicit",
+            Self::Venv => "venv",
+            Self::ToolDir => "tool-dir",
+            Self::Path => "path",
+        }
+    }
+}
+
+impl BackendCommand {
+    pub fn resolve(
+        kind: BackendKind,
+        overrides: Option<&BackendCommandConfig>,
+    ) -> Result<Self, BackendError> {
+        let program = overrides
+            .and_then(|o| o.command.clone())
+            .or_else(|| kind.command().map(str::to_string))
+            .ok_or(BackendError::MissingCommand(kind.display_name()))?;
+        let args = overrides
+            .and_then(|o| o.args.clone())
+            .unwrap_or_else(|| kind.args().iter().map(|a| a.to_string()).collect());
+        let env = overrides
+            .map(|o| o.env.clone().into_iter().collect())
+            .unwrap_or_default();
+
+        Ok(Self {
+            program,
+            args,
+            env,
+            tool_dir: None,
+            require_venv_local: false,
+        })
+    }
+
+    /// Locate the executable for a backend serving `venv`.
+    ///
+    /// Lookup order for bare names: venv `bin/` → `tool_dir` → PATH.
+    /// In require-venv-local mode, anything but the venv's own copy is an error.
+    pub fn locate(
+        &self,
+        kind: BackendKind,
+        venv: Option<&Path>,
+    ) -> Result<(PathBuf, ExecutableSource), BackendError> {
+        if self.program.contains('/') {
+            let path = PathBuf::from(&self.program);
+            return if is_executable(&path) {
+                Ok((path, ExecutableSource::Explicit))
+            } else {
+                Err(BackendError::ExecutableNotFound {
+                    backend: kind.display_name(),
+                    program: self.program.clone(),
+                })
+            };
+        }
+
+        if let Some(venv) = venv {
+            let candidate = venv.join("bin").join(&self.program);
+            if is_executable(&candidate) {
+                return Ok((candidate, ExecutableSource::Venv));
+            }
+            if self.require_venv_local {
+                return Err(BackendError::NotInVenv {
+                    backend: kind.display_name(),
+                    program: self.program.clone(),
+                    venv: venv.to_path_buf(),
+                });
+            }
+        }
+
+        if let Some(candidate) = self
+            .tool_dir
+            .as_ref()
+            .map(|dir| dir.join(&self.program))
+            .filter(|candidate| is_executable(candidate))
+        {
+            return Ok((candidate, ExecutableSource::ToolDir));
+        }
+
+        find_in_path(&self.program)
+            .map(|path| (path, ExecutableSource::Path))
+            .ok_or_else(|| BackendError::ExecutableNotFound {
+                backend: kind.display_name(),
+                program: self.program.clone(),
+            })
+    }
+}
+
+/// Check at startup that every backend the config can select has a runnable command.
+///
+/// Explicitly configured commands (and `custom`) must exist, otherwise startup fails.
+/// A missing built-in command only warns: it may be installed inside each venv.
+/// Venvs are not known yet, so bare names are looked up in `tool_dir` and PATH only,
+/// and not at all in require-venv-local mode.
+pub fn validate_backend_commands(
+    config: &Config,
+    default: BackendKind,
+) -> Result<(), BackendError> {
+    let kinds: BTreeSet<BackendKind> = std::iter::once(default)
+        .chain(config.backend_rules.iter().map(|rule| rule.backend))
+        .chain(config.backends.keys().copied())
+        .collect();
+
+    for kind in kinds {
+        let command = config.backend_command(kind)?;
+        if command.require_venv_local && !command.program.contains('/') {
+            continue;
+        }
+        let configured = config
+            .backends
+            .get(&kind)
+            .is_some_and(|o| o.command.is_some());
+
+        match command.locate(kind, None) {
+            Ok((path, source)) => {
+                tracing::debug!(
+                    backend = kind.display_name(),
+                    executable = %path.display(),
+                    source = source.as_str(),
+                    "Backend executable found"
+                );
+            }
+            Err(e) if configured => return Err(e),
+            Err(_) => {
+                tracing::warn!(
+                    backend = kind.display_name(),
+                    program = %command.program,
+                    "Backend executable not found in PATH; it must be installed in each venv"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Log the executable serving a backend session, with the name and version the
+/// server reported in its initialize response (`serverInfo`)
+pub fn log_backend_executable(
+    kind: BackendKind,
+    (executable, source): (PathBuf, ExecutableSource),
+    venv: &Path,
+    session: u64,
+    init_response: &RpcMessage,
+) {
+    let server_info = init_response
+        .result
+        .as_ref()
+        .and_then(|r| r.get("serverInfo"));
+    let field = |name: &str| {
+        server_info
+            .and_then(|info| info.get(name))
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .to_string()
+    };
+
+    tracing::info!(
+        session = session,
+        backend = kind.display_name(),
+        venv = %venv.display(),
+        executable = %executable.display(),
+        source = source.as_str(),
+        server_name = %field("name"),
+        version = %field("version"),
+        "Backend executable"
+    );
+}
+
+fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
 /// Components returned by `LspBackend::into_split()`
 pub struct BackendParts {
     pub reader: LspFrameReader<ChildStdout>,
@@ -68,6 +297,8 @@ pub struct BackendParts {
 }
 
 pub struct LspBackend {
+    /// Resolved executable and where it was found
+    executable: (PathBuf, ExecutableSource),
     child: Child,
     reader: LspFrameReader<ChildStdout>,
     writer: LspFrameWriter<ChildStdin>,
@@ -78,11 +309,15 @@ impl LspBackend {
     /// Spawn an LSP backend process.
     ///
     /// When venv_path is Some, apply backend-specific environment variables.
-    pub async fn spawn(kind: BackendKind, venv_path: Option<&Path>) -> Result<Self, BackendError> {
-        let mut cmd = Command::new(kind.command());
-        for arg in kind.args() {
-            cmd.arg(arg);
-        }
+    /// The command's extra environment variables are applied last.
+    pub async fn spawn(
+        kind: BackendKind,
+        command: &BackendCommand,
+        venv_path: Option<&Path>,
+    ) -> Result<Self, BackendError> {
+        let (executable, source) = command.locate(kind, venv_path)?;
+        let mut cmd = Command::new(&executable);
+        cmd.args(&command.args);
         cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::inherit())
@@ -103,6 +338,15 @@ impl LspBackend {
                 "Spawning backend without venv"
             );
         }
+        cmd.envs(command.env.iter().map(|(k, v)| (k, v)));
+        tracing::debug!(
+            backend = kind.display_name(),
+            executable = %executable.display(),
+            source = source.as_str(),
+            args = ?command.args,
+            extra_env = ?command.env.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            "Backend command"
+        );
 
         let mut child = cmd.spawn()?;
 
@@ -113,6 +357,7 @@ impl LspBackend {
         let writer = LspFrameWriter::new(stdin);
 
         Ok(Self {
+            executable: (executable, source),
             child,
             reader,
             writer,
@@ -120,6 +365,11 @@ impl LspBackend {
         })
     }
 
+    /// Resolved executable and where it was found
+    pub fn executable(&self) -> (PathBuf, ExecutableSource) {
+        self.executable.clone()
+    }
+
     /// Send message
     pub async fn send_message(&mut self, message: &RpcMessage) -> Result<(), BackendError> {
         self.writer
@@ -369,12 +619,119 @@ mod tests {
 
     #[test]
     fn backend_kind_command_and_args() {
-        assert_eq!(BackendKind::Pyright.command(), "pyright-langserver");
+        assert_eq!(BackendKind::Pyright.command(), Some("pyright-langserver"));
         assert_eq!(BackendKind::Pyright.args(), &["--stdio"]);
-        assert_eq!(BackendKind::Ty.command(), "ty");
+        assert_eq!(BackendKind::Ty.command(), Some("ty"));
         assert_eq!(BackendKind::Ty.args(), &["server"]);
-        assert_eq!(BackendKind::Pyrefly.command(), "pyrefly");
+        assert_eq!(BackendKind::Pyrefly.command(), Some("pyrefly"));
         assert_eq!(BackendKind::Pyrefly.args(), &["lsp"]);
+        assert_eq!(BackendKind::Custom.command(), None);
+    }
+
+    #[test]
+    fn backend_command_overrides() {
+        let default = BackendCommand::resolve(BackendKind::Pyright, None).unwrap();
+        assert_eq!(default.program, "pyright-langserver");
+        assert_eq!(default.args, vec!["--stdio".to_string()]);
+
+        let config: Config = toml::from_str(
+            r#"
+            [backends.pyright]
+            command = "basedpyright-langserver"
+            env = { NODE_OPTIONS = "--max-old-space-size=4096" }
+
+            [backends.custom]
+            command = "/opt/pylsp/bin/pylsp"
+            args = ["--verbose"]
+            "#,
+        )
+        .unwrap();
+
+        let pyright = BackendCommand::resolve(
+            BackendKind::Pyright,
+            config.backends.get(&BackendKind::Pyright),
+        )
+        .unwrap();
+        assert_eq!(pyright.program, "basedpyright-langserver");
+        assert_eq!(pyright.args, vec!["--stdio".to_string()]);
+        assert_eq!(
+            pyright.env,
+            vec![(
+                "NODE_OPTIONS".to_string(),
+                "--max-old-space-size=4096".to_string()
+            )]
+        );
+
+        let custom = BackendCommand::resolve(
+            BackendKind::Custom,
+            config.backends.get(&BackendKind::Custom),
+        )
+        .unwrap();
+        assert_eq!(custom.program, "/opt/pylsp/bin/pylsp");
+        assert_eq!(custom.args, vec!["--verbose".to_string()]);
+
+        assert!(matches!(
+            BackendCommand::resolve(BackendKind::Custom, None),
+            Err(BackendError::MissingCommand("custom"))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn locate_prefers_venv_then_tool_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let venv = temp.path().join(".venv");
+        let tool_dir = temp.path().join("tools");
+        for dir in [venv.join("bin"), tool_dir.clone()] {
+            std::fs::create_dir_all(&dir).unwrap();
+            let exe = dir.join("fake-langserver");
+            std::fs::write(&exe, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let command = BackendCommand {
+            program: "fake-langserver".to_string(),
+            args: Vec::new(),
+            env: Vec::new(),
+            tool_dir: Some(tool_dir.clone()),
+            require_venv_local: false,
+        };
+        let kind = BackendKind::Custom;
+
+        let (path, source) = command.locate(kind, Some(&venv)).unwrap();
+        assert_eq!(path, venv.join("bin/fake-langserver"));
+        assert_eq!(source, ExecutableSource::Venv);
+
+        let other_venv = temp.path().join("other/.venv");
+        let (path, source) = command.locate(kind, Some(&other_venv)).unwrap();
+        assert_eq!(path, tool_dir.join("fake-langserver"));
+        assert_eq!(source, ExecutableSource::ToolDir);
+
+        let strict = BackendCommand {
+            require_venv_local: true,
+            ..command
+        };
+        assert!(matches!(
+            strict.locate(kind, Some(&other_venv)),
+            Err(BackendError::NotInVenv { .. })
+        ));
+    }
+
+    #[test]
+    fn validate_rejects_missing_configured_executable() {
+        let config: Config =
+            toml::from_str("[backends.ty]\ncommand = \"/nonexistent/bin/ty\"\n").unwrap();
+        assert!(matches!(
+            validate_backend_commands(&config, BackendKind::Pyright),
+            Err(BackendError::ExecutableNotFound { backend: "ty", .. })
+        ));
+
+        let config: Config = toml::from_str("[backends.custom]\nargs = [\"--stdio\"]\n").unwrap();
+        assert!(matches!(
+            validate_backend_commands(&config, BackendKind::Custom),
+            Err(BackendError::MissingCommand("custom"))
+        ));
     }
 
     #[test]
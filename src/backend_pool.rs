@@ -1,9 +1,9 @@
-use crate::backend::shutdown_fire_and_forget;
-use crate::error::BackendError;
+use crate::backend::{shutdown_fire_and_forget, BackendKind};
+use crate::error::{BackendError, FramingError};
 use crate::framing::{LspFrameReader, LspFrameWriter};
 use crate::message::{RpcId, RpcMessage};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tokio::process::{Child, ChildStdin, ChildStdout};
 use tokio::sync::mpsc;
@@ -20,17 +20,17 @@ pub enum WarmupState {
     Ready,
 }
 
-/// Default warmup timeout; overridable via `TYPEMUX_CC_WARMUP_TIMEOUT` env var.
-const DEFAULT_WARMUP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Warmup timeout for venvs without observed warmups (see `history::VenvHistory::warmup_timeout`);
+/// overridable via `TYPEMUX_CC_WARMUP_TIMEOUT` env var.
+pub const DEFAULT_WARMUP_TIMEOUT: Duration = Duration::from_secs(2);
 
-/// Returns the warmup timeout duration.
+/// Returns the warmup timeout set via `TYPEMUX_CC_WARMUP_TIMEOUT`, which applies to every venv.
 /// `TYPEMUX_CC_WARMUP_TIMEOUT=0` means warmup is disabled (immediate Ready).
-pub fn warmup_timeout() -> Duration {
+pub fn warmup_timeout_override() -> Option<Duration> {
     std::env::var("TYPEMUX_CC_WARMUP_TIMEOUT")
         .ok()
         .and_then(|v| v.parse::<u64>().ok())
         .map(Duration::from_secs)
-        .unwrap_or(DEFAULT_WARMUP_TIMEOUT)
 }
 
 /// Message from a backend reader task
@@ -45,13 +45,22 @@ pub struct BackendInstance {
     pub writer: LspFrameWriter<ChildStdin>,
     pub child: Child,
     pub venv_path: PathBuf,
+    /// Backend kind serving this venv (one per venv, see `BackendPool::insert`)
+    pub kind: BackendKind,
     pub session: u64,
     pub last_used: Instant,
     pub reader_task: JoinHandle<()>,
     pub next_id: u64,
     pub warmup_state: WarmupState,
     pub warmup_deadline: Instant,
+    /// When warmup started, until the first `$/progress` end measures how long the
+    /// backend took to index (None once measured)
+    pub warmup_started: Option<Instant>,
     pub warmup_queue: Vec<RpcMessage>,
+    /// Resident set size at the last memory sample (None until sampled or if unavailable)
+    pub rss_bytes: Option<u64>,
+    /// When the process was stopped (SIGSTOP) for being idle; None while running
+    pub suspended_at: Option<Instant>,
 }
 
 impl BackendInstance {
@@ -82,11 +91,73 @@ impl BackendInstance {
         queued
     }
 
+    /// Record the first `$/progress` end since spawn, returning how long the initial
+    /// indexing took. Also measured when the warmup timeout already made the backend ready,
+    /// so a too-short tuned timeout can grow.
+    pub fn finish_indexing(&mut self) -> Option<Duration> {
+        self.warmup_started.take().map(|started| started.elapsed())
+    }
+
     /// Check if the warmup deadline has passed
     pub fn warmup_expired(&self) -> bool {
         Instant::now() >= self.warmup_deadline
     }
 
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    /// Stop the backend process (SIGSTOP). Its memory, including the index, is kept
+    /// and it resumes where it left off on the next `send`.
+    pub fn suspend(&mut self) -> std::io::Result<()> {
+        if self.is_suspended() {
+            return Ok(());
+        }
+        let pid = self.child.id().ok_or_else(exited_error)?;
+        signal_process(pid, ProcessSignal::Stop)?;
+        self.suspended_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Continue a suspended backend process (SIGCONT); no-op if it is running
+    pub fn resume(&mut self) {
+        let Some(suspended_at) = self.suspended_at.take() else {
+            return;
+        };
+        let result = self
+            .child
+            .id()
+            .ok_or_else(exited_error)
+            .and_then(|pid| signal_process(pid, ProcessSignal::Continue));
+        match result {
+            Ok(()) => tracing::info!(
+                venv = %self.venv_path.display(),
+                session = self.session,
+                suspended_secs = suspended_at.elapsed().as_secs(),
+                "Resumed suspended backend"
+            ),
+            // An exited process surfaces as a crash through the reader task
+            Err(e) => tracing::warn!(
+                venv = %self.venv_path.display(),
+                error = ?e,
+                "Failed to resume suspended backend"
+            ),
+        }
+    }
+
+    /// Write a message to the backend, resuming it first if it is suspended
+    /// (a stopped process would never drain its stdin)
+    pub async fn send(&mut self, message: &RpcMessage) -> Result<(), FramingError> {
+        self.resume();
+        self.writer.write_message(message).await
+    }
+
+    /// Refresh `rss_bytes` from `/proc/<pid>/status`
+    pub fn sample_rss(&mut self) -> Option<u64> {
+        self.rss_bytes = self.child.id().and_then(read_rss_bytes);
+        self.rss_bytes
+    }
+
     /// Remove a queued request by its JSON-RPC id (for $/cancelRequest handling).
     /// Returns the removed message if found.
     pub fn cancel_warmup_request(&mut self, id: &RpcId) -> Option<RpcMessage> {
@@ -102,7 +173,8 @@ impl BackendInstance {
     }
 }
 
-/// Pool of backend processes keyed by venv path
+/// Pool of backend processes keyed by venv path, so a venv is never served by
+/// two backends (of the same or different kinds) at once
 pub struct BackendPool {
     backends: HashMap<PathBuf, BackendInstance>,
     pub backend_msg_tx: mpsc::Sender<BackendMessage>,
@@ -110,6 +182,8 @@ pub struct BackendPool {
     max_backends: usize,
     backend_ttl: Option<Duration>,
     next_session: u64,
+    /// Venvs whose backends are never evicted and don't count toward `max_backends`
+    pinned: HashSet<PathBuf>,
 }
 
 impl BackendPool {
@@ -122,42 +196,68 @@ impl BackendPool {
             max_backends,
             backend_ttl,
             next_session: 0,
+            pinned: HashSet::new(),
         }
     }
 
-    /// Get immutable reference to a backend instance
-    pub fn get(&self, venv_path: &PathBuf) -> Option<&BackendInstance> {
+    /// Get immutable reference to the backend instance of a venv
+    pub fn get(&self, venv_path: &Path) -> Option<&BackendInstance> {
         self.backends.get(venv_path)
     }
 
-    /// Get mutable reference to a backend instance
-    pub fn get_mut(&mut self, venv_path: &PathBuf) -> Option<&mut BackendInstance> {
+    /// Get mutable reference to the backend instance of a venv
+    pub fn get_mut(&mut self, venv_path: &Path) -> Option<&mut BackendInstance> {
         self.backends.get_mut(venv_path)
     }
 
     /// Check if a backend exists for the given venv path
-    pub fn contains(&self, venv_path: &PathBuf) -> bool {
+    pub fn contains(&self, venv_path: &Path) -> bool {
         self.backends.contains_key(venv_path)
     }
 
-    /// Insert a backend instance into the pool
-    pub fn insert(&mut self, venv_path: PathBuf, instance: BackendInstance) {
-        self.backends.insert(venv_path, instance);
+    /// Insert a backend instance into the pool. Returns the backend it displaced
+    /// (the venv's previous one, of any kind), which the caller must tear down.
+    pub fn insert(&mut self, instance: BackendInstance) -> Option<BackendInstance> {
+        self.backends.insert(instance.venv_path.clone(), instance)
     }
 
-    /// Remove a backend instance from the pool
-    pub fn remove(&mut self, venv_path: &PathBuf) -> Option<BackendInstance> {
+    /// Remove the backend instance of a venv from the pool
+    pub fn remove(&mut self, venv_path: &Path) -> Option<BackendInstance> {
         self.backends.remove(venv_path)
     }
 
-    /// Find the LRU (least recently used) venv path.
+    /// Pin or unpin a venv (it need not be in the pool). Returns whether the state changed.
+    pub fn set_pinned(&mut self, venv_path: PathBuf, pinned: bool) -> bool {
+        if pinned {
+            self.pinned.insert(venv_path)
+        } else {
+            self.pinned.remove(&venv_path)
+        }
+    }
+
+    pub fn is_pinned(&self, venv_path: &Path) -> bool {
+        self.pinned.contains(venv_path)
+    }
+
+    /// Pinned venvs, sorted
+    pub fn pinned_venvs(&self) -> Vec<PathBuf> {
+        let mut pinned: Vec<PathBuf> = self.pinned.iter().cloned().collect();
+        pinned.sort();
+        pinned
+    }
+
+    /// Find the LRU (least recently used) unpinned venv path.
     /// Prefers backends with no pending requests (caller provides the count).
-    /// Returns None if pool is empty.
+    /// Returns None if the pool has no unpinned backends.
     pub fn lru_venv(&self, pending_count_fn: impl Fn(&PathBuf, u64) -> usize) -> Option<PathBuf> {
+        let evictable = || {
+            self.backends
+                .iter()
+                .filter(|(venv, _)| !self.pinned.contains(*venv))
+        };
+
         // First try: find LRU among backends with 0 pending requests
-        let no_pending_lru = self
-            .backends
-            .iter()
+        let no_pending_lru = evictable()
             .filter(|(venv, inst)| pending_count_fn(venv, inst.session) == 0)
             .min_by_key(|(_, inst)| inst.last_used)
             .map(|(venv, _)| venv.clone());
@@ -166,22 +266,63 @@ impl BackendPool {
             return no_pending_lru;
         }
 
-        // Fallback: LRU among all backends
-        self.backends
-            .iter()
+        // Fallback: LRU among all unpinned backends
+        evictable()
             .min_by_key(|(_, inst)| inst.last_used)
             .map(|(venv, _)| venv.clone())
     }
 
+    /// Backends to evict, in order, to bring `total` sampled RSS within `budget`, each with
+    /// the RSS it frees. Candidates are picked like `lru_venv` (unpinned, LRU first,
+    /// preferring backends with no pending requests); the last backend is never evicted.
+    pub fn over_budget_evictions(
+        &self,
+        mut total: u64,
+        budget: u64,
+        pending_count_fn: impl Fn(&PathBuf, u64) -> usize,
+    ) -> Vec<(PathBuf, u64)> {
+        let mut candidates: Vec<(&PathBuf, &BackendInstance)> = self
+            .backends
+            .iter()
+            .filter(|(venv, _)| !self.pinned.contains(*venv))
+            .collect();
+        candidates
+            .sort_by_key(|(venv, inst)| (pending_count_fn(venv, inst.session) > 0, inst.last_used));
+
+        let mut remaining = self.backends.len();
+        let mut evictions = Vec::new();
+        for (venv, inst) in candidates {
+            if total <= budget || remaining <= 1 {
+                break;
+            }
+            let rss = inst.rss_bytes.unwrap_or_default();
+            total = total.saturating_sub(rss);
+            remaining -= 1;
+            evictions.push((venv.clone(), rss));
+        }
+        evictions
+    }
+
     /// Generate a new unique session ID
     pub fn next_session_id(&mut self) -> u64 {
         self.next_session += 1;
         self.next_session
     }
 
-    /// Check if pool is at capacity
-    pub fn is_full(&self) -> bool {
-        self.backends.len() >= self.max_backends
+    /// Check if pool is at capacity, counting `booting` unpinned backends that will join it.
+    /// Pinned backends don't count.
+    pub fn is_full(&self, booting: usize) -> bool {
+        self.free_slots(booting) == 0
+    }
+
+    /// Unpinned backends that can still join the pool without an eviction
+    pub fn free_slots(&self, booting: usize) -> usize {
+        let unpinned = self
+            .backends
+            .keys()
+            .filter(|venv| !self.pinned.contains(*venv))
+            .count();
+        self.max_backends.saturating_sub(unpinned + booting)
     }
 
     /// Number of backends in the pool
@@ -199,7 +340,7 @@ impl BackendPool {
         self.max_backends
     }
 
-    /// Return venv paths of backends whose last_used exceeds the TTL.
+    /// Return venv paths of unpinned backends whose last_used exceeds the TTL.
     /// Only checks TTL/last_used; pending request filtering is the caller's responsibility.
     pub fn expired_venvs(&self) -> Vec<PathBuf> {
         let ttl = match self.backend_ttl {
@@ -210,7 +351,25 @@ impl BackendPool {
         let now = Instant::now();
         self.backends
             .iter()
-            .filter(|(_, inst)| now.duration_since(inst.last_used) >= ttl)
+            .filter(|(venv, inst)| {
+                !self.pinned.contains(*venv) && now.duration_since(inst.last_used) >= ttl
+            })
+            .map(|(venv, _)| venv.clone())
+            .collect()
+    }
+
+    /// Return venv paths of running, ready, unpinned backends idle for at least `idle_for`.
+    /// Pending request filtering is the caller's responsibility.
+    pub fn idle_venvs(&self, idle_for: Duration) -> Vec<PathBuf> {
+        let now = Instant::now();
+        self.backends
+            .iter()
+            .filter(|(venv, inst)| {
+                !self.pinned.contains(*venv)
+                    && !inst.is_warming()
+                    && !inst.is_suspended()
+                    && now.duration_since(inst.last_used) >= idle_for
+            })
             .map(|(venv, _)| venv.clone())
             .collect()
     }
@@ -225,11 +384,19 @@ impl BackendPool {
         self.backends.keys().cloned().collect()
     }
 
-    /// Get the first key in the map (arbitrary, for fallback routing)
+    /// Get the venv of the first key in the map (arbitrary, for fallback routing)
     pub fn first_key(&self) -> Option<&PathBuf> {
         self.backends.keys().next()
     }
 
+    /// Sample every backend's RSS and return the total (backends without a sample count as 0)
+    pub fn sample_total_rss(&mut self) -> u64 {
+        self.backends
+            .values_mut()
+            .filter_map(|inst| inst.sample_rss())
+            .sum()
+    }
+
     /// Return venv paths of backends currently in Warming state
     pub fn warming_backends(&self) -> Vec<PathBuf> {
         self.backends
@@ -250,6 +417,57 @@ impl BackendPool {
     }
 }
 
+/// Read a process's resident set size (Linux only; None elsewhere or if it exited)
+fn read_rss_bytes(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    parse_vm_rss(&status)
+}
+
+/// Parse the `VmRSS:` line of `/proc/<pid>/status` into bytes
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let mut fields = line["VmRSS:".len()..].split_whitespace();
+    let value: u64 = fields.next()?.parse().ok()?;
+    match fields.next() {
+        Some("kB") | None => Some(value * 1024),
+        Some(_) => None,
+    }
+}
+
+/// Job-control signals used to suspend idle backends
+#[derive(Debug, Clone, Copy)]
+enum ProcessSignal {
+    Stop,
+    Continue,
+}
+
+#[cfg(unix)]
+fn signal_process(pid: u32, signal: ProcessSignal) -> std::io::Result<()> {
+    let signal = match signal {
+        ProcessSignal::Stop => libc::SIGSTOP,
+        ProcessSignal::Continue => libc::SIGCONT,
+    };
+    let pid = libc::pid_t::try_from(pid).map_err(std::io::Error::other)?;
+    // SAFETY: kill(2) has no memory-safety preconditions; `pid` is our own child
+    if unsafe { libc::kill(pid, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn signal_process(_pid: u32, _signal: ProcessSignal) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "suspending processes is only supported on Unix",
+    ))
+}
+
+fn exited_error() -> std::io::Error {
+    std::io::Error::other("backend process has exited")
+}
+
 /// Spawn a reader task that reads messages from a backend and sends them to the channel
 pub fn spawn_reader_task(
     mut reader: LspFrameReader<ChildStdout>,
@@ -296,8 +514,10 @@ pub fn spawn_reader_task(
 }
 
 /// Shutdown and clean up a backend instance (abort reader, fire-and-forget shutdown)
-pub fn shutdown_backend_instance(instance: BackendInstance) {
+pub fn shutdown_backend_instance(mut instance: BackendInstance) {
     instance.reader_task.abort();
+    // A stopped process would neither read the shutdown request nor die from SIGTERM
+    instance.resume();
     let venv_display = instance.venv_path.display().to_string();
     shutdown_fire_and_forget(
         instance.writer,
@@ -306,3 +526,217 @@ pub fn shutdown_backend_instance(instance: BackendInstance) {
         venv_display,
     );
 }
+
+#[cfg(test)]
+impl BackendInstance {
+    /// A ready backend instance around a `cat` process, last used `idle` ago
+    pub(crate) fn stub(venv: &str, kind: BackendKind, idle: Duration) -> Self {
+        let mut child = tokio::process::Command::new("cat")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let now = Instant::now();
+        Self {
+            writer: LspFrameWriter::new(stdin),
+            child,
+            venv_path: PathBuf::from(venv),
+            kind,
+            session: 1,
+            last_used: now - idle,
+            reader_task: tokio::spawn(async {}),
+            next_id: 1,
+            warmup_state: WarmupState::Ready,
+            warmup_deadline: now,
+            warmup_started: None,
+            warmup_queue: Vec::new(),
+            rss_bytes: None,
+            suspended_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn venv_is_served_by_one_backend() {
+        let mut pool = BackendPool::new(4, None);
+        let venv = PathBuf::from("/a/.venv");
+        let displaced = pool.insert(BackendInstance::stub(
+            "/a/.venv",
+            BackendKind::Pyright,
+            Duration::ZERO,
+        ));
+        assert!(displaced.is_none());
+
+        // A backend of another kind for the same venv replaces it
+        let displaced = pool.insert(BackendInstance::stub(
+            "/a/.venv",
+            BackendKind::Ty,
+            Duration::ZERO,
+        ));
+        assert_eq!(displaced.map(|old| old.kind), Some(BackendKind::Pyright));
+        assert_eq!(pool.get(&venv).map(|inst| inst.kind), Some(BackendKind::Ty));
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pinned_backends_are_outside_capacity_and_eviction() {
+        let mut pool = BackendPool::new(2, Some(Duration::from_secs(60)));
+        for (venv, idle_secs) in [("/a/.venv", 300), ("/b/.venv", 120), ("/c/.venv", 0)] {
+            pool.insert(BackendInstance::stub(
+                venv,
+                BackendKind::Pyright,
+                Duration::from_secs(idle_secs),
+            ));
+        }
+        assert_eq!(pool.free_slots(0), 0);
+
+        pool.set_pinned(PathBuf::from("/a/.venv"), true);
+        assert_eq!(pool.len(), 3);
+        assert!(pool.is_full(0));
+        assert_eq!(pool.free_slots(0), 0);
+        assert_eq!(pool.expired_venvs(), vec![PathBuf::from("/b/.venv")]);
+        assert_eq!(pool.lru_venv(|_, _| 0), Some(PathBuf::from("/b/.venv")));
+
+        pool.remove(Path::new("/b/.venv"));
+        assert!(!pool.is_full(0));
+        assert_eq!(pool.free_slots(0), 1);
+        assert!(pool.is_full(1));
+        assert!(pool.expired_venvs().is_empty());
+        assert_eq!(pool.lru_venv(|_, _| 0), Some(PathBuf::from("/c/.venv")));
+
+        // Only pinned backends left: nothing to evict
+        pool.remove(Path::new("/c/.venv"));
+        assert_eq!(pool.free_slots(0), 2);
+        assert_eq!(pool.lru_venv(|_, _| 0), None);
+    }
+
+    #[tokio::test]
+    async fn idle_venvs_selects_running_ready_unpinned_backends() {
+        let mut pool = BackendPool::new(8, None);
+        for venv in ["/idle", "/recent", "/pinned", "/warming", "/suspended"] {
+            let idle = if venv == "/recent" { 10 } else { 120 };
+            pool.insert(BackendInstance::stub(
+                venv,
+                BackendKind::Pyright,
+                Duration::from_secs(idle),
+            ));
+        }
+        pool.set_pinned(PathBuf::from("/pinned"), true);
+        pool.get_mut(Path::new("/warming")).unwrap().warmup_state = WarmupState::Warming;
+        pool.get_mut(Path::new("/suspended")).unwrap().suspended_at = Some(Instant::now());
+
+        assert_eq!(
+            pool.idle_venvs(Duration::from_secs(60)),
+            vec![PathBuf::from("/idle")]
+        );
+        let mut idle = pool.idle_venvs(Duration::from_secs(5));
+        idle.sort();
+        assert_eq!(idle, vec![PathBuf::from("/idle"), PathBuf::from("/recent")]);
+    }
+
+    #[tokio::test]
+    async fn memory_budget_evicts_lru_unpinned_first() {
+        const MIB: u64 = 1024 * 1024;
+        let mut pool = BackendPool::new(8, None);
+        for (venv, idle_secs, rss_mb) in [
+            ("/a/.venv", 30, 300),
+            ("/b/.venv", 20, 200),
+            ("/c/.venv", 10, 1000),
+            ("/d/.venv", 0, 100),
+        ] {
+            let mut inst =
+                BackendInstance::stub(venv, BackendKind::Pyright, Duration::from_secs(idle_secs));
+            inst.rss_bytes = Some(rss_mb * MIB);
+            pool.insert(inst);
+        }
+        pool.set_pinned(PathBuf::from("/c/.venv"), true);
+        let total = pool.backends.values().filter_map(|i| i.rss_bytes).sum();
+        // /a/.venv is the least recently used, but has a pending request
+        let pending = |venv: &PathBuf, _session: u64| usize::from(venv.ends_with("a/.venv"));
+        let evicted = |pool: &BackendPool, budget_mb: u64| -> Vec<(String, u64)> {
+            pool.over_budget_evictions(total, budget_mb * MIB, pending)
+                .into_iter()
+                .map(|(venv, rss)| (venv.display().to_string(), rss / MIB))
+                .collect()
+        };
+        let e = |venv: &str, rss_mb: u64| (venv.to_string(), rss_mb);
+
+        assert!(evicted(&pool, 1600).is_empty());
+        assert_eq!(
+            evicted(&pool, 1300),
+            vec![e("/b/.venv", 200), e("/d/.venv", 100)]
+        );
+        // The pinned backend is never a candidate
+        assert_eq!(
+            evicted(&pool, 0),
+            vec![e("/b/.venv", 200), e("/d/.venv", 100), e("/a/.venv", 300)]
+        );
+
+        // Without pins the last backend still stays
+        pool.set_pinned(PathBuf::from("/c/.venv"), false);
+        assert_eq!(
+            evicted(&pool, 0),
+            vec![e("/b/.venv", 200), e("/c/.venv", 1000), e("/d/.venv", 100)]
+        );
+    }
+
+    #[tokio::test]
+    async fn indexing_is_measured_after_warmup_timeout() {
+        let mut inst = BackendInstance::stub("/a/.venv", BackendKind::Pyright, Duration::ZERO);
+        inst.warmup_state = WarmupState::Warming;
+        inst.warmup_started = Some(Instant::now() - Duration::from_secs(5));
+
+        // The warmup timeout fires first; indexing ends later
+        inst.mark_ready();
+        let warmup = inst.finish_indexing();
+        assert!(warmup.is_some_and(|w| w >= Duration::from_secs(5)));
+
+        // Later progress (re-analysis) is not a warmup
+        assert_eq!(inst.finish_indexing(), None);
+    }
+
+    #[test]
+    fn parse_vm_rss_from_proc_status() {
+        let status = "Name:\tnode\nVmPeak:\t 2048 kB\nVmRSS:\t  524288 kB\nThreads:\t12\n";
+        assert_eq!(parse_vm_rss(status), Some(512 * 1024 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tzombie\n"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn stop_and_continue_process() {
+        let process_state = |pid: u32| {
+            let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+            let line = status.lines().find(|l| l.starts_with("State:")).unwrap();
+            line["State:".len()..].trim().chars().next().unwrap()
+        };
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+
+        signal_process(pid, ProcessSignal::Stop).unwrap();
+        let stopped = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            process_state(pid) == 'T'
+        });
+        signal_process(pid, ProcessSignal::Continue).unwrap();
+        let running = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            process_state(pid) != 'T'
+        });
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(stopped, "process was not stopped");
+        assert!(running, "process was not continued");
+    }
+}
@@ -0,0 +1,720 @@
+use crate::backend::{BackendCommand, BackendKind};
+use crate::error::{BackendError, ConfigError};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Project config file name, looked up at the git toplevel
+pub const CONFIG_FILE: &str = ".typemux.toml";
+
+/// Default candidate venv directory name
+pub const DEFAULT_VENV_DIR: &str = ".venv";
+
+/// Default file marking a directory as a venv
+pub const DEFAULT_VENV_MARKER: &str = "pyvenv.cfg";
+
+/// Default number of crashes within the crash window before a venv is quarantined
+pub const DEFAULT_CRASH_LIMIT: u32 = 5;
+
+/// Default crash window in seconds
+pub const DEFAULT_CRASH_WINDOW_SECS: u64 = 300;
+
+/// Default unresponsiveness threshold in seconds
+pub const DEFAULT_HANG_TIMEOUT_SECS: u64 = 120;
+
+/// Default number of backends started by pre-warming
+pub const DEFAULT_PREWARM_BUDGET: usize = 2;
+
+/// Default time to wait for all backends' answers to a fanned-out request, in seconds
+pub const DEFAULT_FANOUT_TIMEOUT_SECS: u64 = 5;
+
+/// Default client request timeout in seconds
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 60;
+
+/// Project-level configuration loaded from `.typemux.toml`.
+///
+/// Example:
+/// ```toml
+/// venv_dirs = [".venv", "venv"]
+///
+/// [[venv_rules]]
+/// pattern = "services/*"
+/// venv = "tools/envs/services-venv"
+///
+/// [[backend_rules]]
+/// venv = "packages/rust-*/.venv"
+/// backend = "ty"
+///
+/// [backends.pyright]
+/// command = "basedpyright-langserver"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Candidate venv directory names, checked in order at every level of the
+    /// parent walk. When a directory contains several, the earliest entry wins.
+    pub venv_dirs: Vec<String>,
+
+    /// Files marking a candidate directory as a venv (any of them; default `pyvenv.cfg`)
+    pub venv_markers: Vec<String>,
+
+    /// Ordered glob → venv rules, evaluated before the `.venv` parent walk.
+    /// The first matching rule wins.
+    pub venv_rules: Vec<VenvRule>,
+
+    /// Ordered venv glob → backend kind rules. The first matching rule wins over
+    /// `[tool.typemux] backend` in the venv's `pyproject.toml`.
+    pub backend_rules: Vec<BackendRule>,
+
+    /// Command line overrides per backend kind (`[backends.<kind>]`)
+    pub backends: HashMap<BackendKind, BackendCommandConfig>,
+
+    /// Directory searched for backend executables after the venv's `bin/` and
+    /// before PATH (relative paths are resolved against the git toplevel)
+    pub backend_tool_dir: Option<PathBuf>,
+
+    /// Only use backend executables installed in the target venv
+    pub require_venv_backend: bool,
+
+    /// How the proxy's own activated env (`VIRTUAL_ENV` / `CONDA_PREFIX`) is used
+    pub activated_env: ActivatedEnvMode,
+
+    /// Route files of uv workspace members to the workspace root venv,
+    /// even if a (stale) venv exists closer to the file
+    pub prefer_uv_workspace_venv: bool,
+
+    /// Watch open documents' directories for venvs being created or removed
+    /// and move the documents to the right backend
+    pub watch_venvs: bool,
+
+    /// Restart a pooled backend when its venv's installed packages change
+    /// (`site-packages`, `pyvenv.cfg`, `uv.lock` / `poetry.lock`)
+    pub restart_on_package_change: bool,
+
+    /// Crashes within `crash_window_secs` after which a venv's backend is no longer
+    /// respawned (quarantined)
+    pub crash_limit: u32,
+
+    /// Window for counting backend crashes, in seconds
+    pub crash_window_secs: u64,
+
+    /// Seconds a backend may leave its oldest request (or probe) unanswered before
+    /// it is considered hung and recycled (0 = disabled)
+    pub hang_timeout_secs: u64,
+
+    /// Seconds between liveness probes sent to ready backends (0 = disabled)
+    pub health_probe_interval_secs: u64,
+
+    /// Seconds a client request may wait for its response before the proxy answers
+    /// it with a timeout error (0 = no timeout)
+    pub request_timeout_secs: u64,
+
+    /// Per-method request timeouts in seconds, overriding `request_timeout_secs`
+    /// (`[request_timeouts]`, e.g. `"workspace/symbol" = 120`; 0 = no timeout)
+    pub request_timeouts: HashMap<String, u64>,
+
+    /// Seconds to wait for every backend's answer to a fanned-out request
+    /// (`workspace/symbol` with several backends) before returning partial results
+    /// (0 = wait for all backends)
+    pub fanout_timeout_secs: u64,
+
+    /// Total resident memory of all backends, in MiB, above which LRU backends are
+    /// evicted (0 = no budget; only `max_backends` applies)
+    pub memory_budget_mb: u64,
+
+    /// Seconds a ready backend may be idle before its process is suspended (SIGSTOP)
+    /// until its next request; 0 = never suspend. The backend TTL still applies.
+    pub suspend_ttl_secs: u64,
+
+    /// Venvs whose backends are never evicted (TTL, LRU, memory budget) and don't
+    /// count toward `max_backends` (relative paths are resolved against the git toplevel)
+    pub pinned_venvs: Vec<PathBuf>,
+
+    /// Files and directories used by several projects (e.g. a common `libs/`); their
+    /// `workspace/didChangeWatchedFiles` changes go to every backend
+    /// (relative paths are resolved against the git toplevel)
+    pub shared_paths: Vec<PathBuf>,
+
+    /// After startup, boot backends for the most recently used venvs under the git
+    /// toplevel in the background
+    pub prewarm: bool,
+
+    /// Maximum number of backends started by pre-warming (free pool slots permitting)
+    pub prewarm_budget: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            venv_dirs: vec![DEFAULT_VENV_DIR.to_string()],
+            venv_markers: vec![DEFAULT_VENV_MARKER.to_string()],
+            venv_rules: Vec::new(),
+            backend_rules: Vec::new(),
+            backends: HashMap::new(),
+            backend_tool_dir: None,
+            require_venv_backend: false,
+            activated_env: ActivatedEnvMode::default(),
+            prefer_uv_workspace_venv: true,
+            watch_venvs: true,
+            restart_on_package_change: true,
+            crash_limit: DEFAULT_CRASH_LIMIT,
+            crash_window_secs: DEFAULT_CRASH_WINDOW_SECS,
+            hang_timeout_secs: DEFAULT_HANG_TIMEOUT_SECS,
+            health_probe_interval_secs: 0,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            request_timeouts: HashMap::new(),
+            fanout_timeout_secs: DEFAULT_FANOUT_TIMEOUT_SECS,
+            memory_budget_mb: 0,
+            suspend_ttl_secs: 0,
+            pinned_venvs: Vec::new(),
+            shared_paths: Vec::new(),
+            prewarm: false,
+            prewarm_budget: DEFAULT_PREWARM_BUDGET,
+        }
+    }
+}
+
+/// Resolution mode for an env activated in the shell that launched the proxy
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActivatedEnvMode {
+    /// Never use the activated env
+    #[default]
+    Ignore,
+    /// Use it as the startup fallback backend (before git toplevel / cwd)
+    Fallback,
+    /// Also use it for files with no venv ancestor
+    CatchAll,
+}
+
+/// Settings from CLI flags / environment variables.
+/// Applied on top of `.typemux.toml` (CLI wins).
+#[derive(Debug, Default, Clone)]
+pub struct ConfigOverrides {
+    pub venv_dirs: Option<Vec<String>>,
+    pub venv_markers: Option<Vec<String>>,
+    pub activated_env: Option<ActivatedEnvMode>,
+    pub prefer_uv_workspace_venv: Option<bool>,
+    pub watch_venvs: Option<bool>,
+    pub restart_on_package_change: Option<bool>,
+    pub backend_tool_dir: Option<PathBuf>,
+    pub require_venv_backend: Option<bool>,
+    pub crash_limit: Option<u32>,
+    pub crash_window_secs: Option<u64>,
+    pub hang_timeout_secs: Option<u64>,
+    pub health_probe_interval_secs: Option<u64>,
+    pub request_timeout_secs: Option<u64>,
+    pub fanout_timeout_secs: Option<u64>,
+    pub memory_budget_mb: Option<u64>,
+    pub suspend_ttl_secs: Option<u64>,
+    pub pinned_venvs: Option<Vec<PathBuf>>,
+    pub shared_paths: Option<Vec<PathBuf>>,
+    pub prewarm: Option<bool>,
+    pub prewarm_budget: Option<usize>,
+}
+
+/// Maps files matching a glob to a fixed venv
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VenvRule {
+    /// Glob matched against the file path relative to the git toplevel
+    /// (`*` also matches across `/`, so `services/*` covers nested files)
+    pub pattern: Glob,
+    /// Venv directory; relative paths are resolved against the git toplevel
+    pub venv: PathBuf,
+}
+
+/// Selects the LSP backend for venvs matching a glob
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackendRule {
+    /// Glob matched against the venv path relative to the git toplevel
+    /// (the absolute path for venvs outside it, e.g. Poetry's cache)
+    pub venv: Glob,
+    pub backend: BackendKind,
+}
+
+/// `[backends.<kind>]`: overrides for the command used to spawn a backend
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackendCommandConfig {
+    /// Executable name (searched in PATH) or path; required for `custom`
+    pub command: Option<String>,
+    /// Arguments replacing the built-in ones (e.g. `["--stdio", "--verbose"]`)
+    pub args: Option<Vec<String>>,
+    /// Extra environment variables for the backend process
+    pub env: BTreeMap<String, String>,
+}
+
+/// A validated glob pattern
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Glob(glob::Pattern);
+
+impl Glob {
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.0.matches_path(path)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl TryFrom<String> for Glob {
+    type Error = glob::PatternError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        glob::Pattern::new(&value).map(Glob)
+    }
+}
+
+impl Config {
+    /// Load `.typemux.toml` from the git toplevel.
+    /// A missing file (or no toplevel) yields the default config.
+    pub fn load(git_toplevel: Option<&Path>) -> Result<Self, ConfigError> {
+        let Some(toplevel) = git_toplevel else {
+            return Ok(Self::default());
+        };
+
+        let path = toplevel.join(CONFIG_FILE);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!(path = %path.display(), "No project config file");
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(ConfigError::Io { path, source: e }),
+        };
+
+        let mut config: Config = toml::from_str(&content).map_err(|e| ConfigError::Parse {
+            path: path.clone(),
+            source: e,
+        })?;
+        if let Some(tool_dir) = &config.backend_tool_dir {
+            config.backend_tool_dir = Some(toplevel.join(tool_dir));
+        }
+        config.pinned_venvs = config
+            .pinned_venvs
+            .iter()
+            .map(|venv| toplevel.join(venv))
+            .collect();
+        config.shared_paths = config
+            .shared_paths
+            .iter()
+            .map(|path| toplevel.join(path))
+            .collect();
+
+        tracing::info!(
+            path = %path.display(),
+            venv_rules = config.venv_rules.len(),
+            "Loaded project config"
+        );
+        Ok(config)
+    }
+
+    /// Apply CLI / environment overrides and validate the result.
+    pub fn with_overrides(mut self, overrides: &ConfigOverrides) -> Result<Self, ConfigError> {
+        if let Some(venv_dirs) = &overrides.venv_dirs {
+            self.venv_dirs = venv_dirs.clone();
+        }
+        if let Some(markers) = &overrides.venv_markers {
+            self.venv_markers = markers.clone();
+        }
+        if let Some(mode) = overrides.activated_env {
+            self.activated_env = mode;
+        }
+        if let Some(prefer) = overrides.prefer_uv_workspace_venv {
+            self.prefer_uv_workspace_venv = prefer;
+        }
+        if let Some(watch) = overrides.watch_venvs {
+            self.watch_venvs = watch;
+        }
+        if let Some(restart) = overrides.restart_on_package_change {
+            self.restart_on_package_change = restart;
+        }
+        if let Some(tool_dir) = &overrides.backend_tool_dir {
+            // Relative to the working directory, like other CLI paths
+            let cwd = std::env::current_dir().unwrap_or_default();
+            self.backend_tool_dir = Some(cwd.join(tool_dir));
+        }
+        if let Some(require) = overrides.require_venv_backend {
+            self.require_venv_backend = require;
+        }
+        if let Some(limit) = overrides.crash_limit {
+            self.crash_limit = limit;
+        }
+        if let Some(window) = overrides.crash_window_secs {
+            self.crash_window_secs = window;
+        }
+        if let Some(timeout) = overrides.hang_timeout_secs {
+            self.hang_timeout_secs = timeout;
+        }
+        if let Some(interval) = overrides.health_probe_interval_secs {
+            self.health_probe_interval_secs = interval;
+        }
+        if let Some(timeout) = overrides.request_timeout_secs {
+            self.request_timeout_secs = timeout;
+        }
+        if let Some(timeout) = overrides.fanout_timeout_secs {
+            self.fanout_timeout_secs = timeout;
+        }
+        if let Some(budget) = overrides.memory_budget_mb {
+            self.memory_budget_mb = budget;
+        }
+        if let Some(ttl) = overrides.suspend_ttl_secs {
+            self.suspend_ttl_secs = ttl;
+        }
+        if let Some(pinned) = &overrides.pinned_venvs {
+            self.pinned_venvs = pinned.clone();
+        }
+        if let Some(shared) = &overrides.shared_paths {
+            self.shared_paths = shared.clone();
+        }
+        if let Some(prewarm) = overrides.prewarm {
+            self.prewarm = prewarm;
+        }
+        if let Some(budget) = overrides.prewarm_budget {
+            self.prewarm_budget = budget;
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.venv_dirs.is_empty() {
+            return Err(ConfigError::Invalid(
+                "venv_dirs must contain at least one directory name".to_string(),
+            ));
+        }
+        if self.venv_markers.is_empty() {
+            return Err(ConfigError::Invalid(
+                "venv_markers must contain at least one file name".to_string(),
+            ));
+        }
+        for (key, name) in self
+            .venv_dirs
+            .iter()
+            .map(|name| ("venv_dirs", name))
+            .chain(self.venv_markers.iter().map(|name| ("venv_markers", name)))
+        {
+            let mut components = Path::new(name).components();
+            let is_single_name = matches!(
+                (components.next(), components.next()),
+                (Some(std::path::Component::Normal(_)), None)
+            );
+            if !is_single_name {
+                return Err(ConfigError::Invalid(format!(
+                    "{} entry '{}' must be a plain name",
+                    key, name
+                )));
+            }
+        }
+        if self.crash_limit == 0 {
+            return Err(ConfigError::Invalid(
+                "crash_limit must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Window for counting backend crashes
+    pub fn crash_window(&self) -> Duration {
+        Duration::from_secs(self.crash_window_secs)
+    }
+
+    /// Unresponsiveness threshold for hang detection (None = disabled)
+    pub fn hang_timeout(&self) -> Option<Duration> {
+        (self.hang_timeout_secs > 0).then(|| Duration::from_secs(self.hang_timeout_secs))
+    }
+
+    /// Timeout for a client request with `method` (None = wait indefinitely)
+    pub fn request_timeout(&self, method: &str) -> Option<Duration> {
+        let secs = self
+            .request_timeouts
+            .get(method)
+            .copied()
+            .unwrap_or(self.request_timeout_secs);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Deadline for fanned-out requests (None = wait for every backend)
+    pub fn fanout_timeout(&self) -> Option<Duration> {
+        (self.fanout_timeout_secs > 0).then(|| Duration::from_secs(self.fanout_timeout_secs))
+    }
+
+    /// Backend memory budget in bytes (None = no budget)
+    pub fn memory_budget_bytes(&self) -> Option<u64> {
+        (self.memory_budget_mb > 0).then(|| self.memory_budget_mb * 1024 * 1024)
+    }
+
+    /// Idle time after which backends are suspended (None = never suspend)
+    pub fn suspend_ttl(&self) -> Option<Duration> {
+        (self.suspend_ttl_secs > 0).then(|| Duration::from_secs(self.suspend_ttl_secs))
+    }
+
+    /// Interval between liveness probes (None = disabled)
+    pub fn health_probe_interval(&self) -> Option<Duration> {
+        (self.health_probe_interval_secs > 0)
+            .then(|| Duration::from_secs(self.health_probe_interval_secs))
+    }
+
+    /// Find the first venv rule matching `file_path` (relative to `git_toplevel`).
+    pub fn match_venv_rule(&self, file_path: &Path, git_toplevel: &Path) -> Option<&VenvRule> {
+        let relative = file_path.strip_prefix(git_toplevel).ok()?;
+        self.venv_rules
+            .iter()
+            .find(|rule| rule.pattern.matches_path(relative))
+    }
+
+    /// Whether `path` is (or is under) one of `shared_paths`
+    pub fn is_shared_path(&self, path: &Path) -> bool {
+        self.shared_paths
+            .iter()
+            .any(|shared| path.starts_with(shared))
+    }
+
+    /// Command line for spawning a backend of `kind`
+    pub fn backend_command(&self, kind: BackendKind) -> Result<BackendCommand, BackendError> {
+        Ok(BackendCommand {
+            tool_dir: self.backend_tool_dir.clone(),
+            require_venv_local: self.require_venv_backend,
+            ..BackendCommand::resolve(kind, self.backends.get(&kind))?
+        })
+    }
+
+    /// Find the first backend rule matching `venv`
+    pub fn match_backend_rule(
+        &self,
+        venv: &Path,
+        git_toplevel: Option<&Path>,
+    ) -> Option<&BackendRule> {
+        let relative = git_toplevel
+            .and_then(|toplevel| venv.strip_prefix(toplevel).ok())
+            .unwrap_or(venv);
+        self.backend_rules
+            .iter()
+            .find(|rule| rule.venv.matches_path(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_venv_rules_in_order() {
+        let config: Config = toml::from_str(
+            r#"
+            [[venv_rules]]
+            pattern = "services/legacy/*"
+            venv = "legacy/.venv"
+
+            [[venv_rules]]
+            pattern = "services/*"
+            venv = "tools/envs/services-venv"
+            "#,
+        )
+        .unwrap();
+
+        let top = Path::new("/repo");
+        let rule = config
+            .match_venv_rule(Path::new("/repo/services/api/main.py"), top)
+            .unwrap();
+        assert_eq!(rule.venv, PathBuf::from("tools/envs/services-venv"));
+
+        let rule = config
+            .match_venv_rule(Path::new("/repo/services/legacy/app.py"), top)
+            .unwrap();
+        assert_eq!(rule.venv, PathBuf::from("legacy/.venv"));
+
+        assert!(config
+            .match_venv_rule(Path::new("/repo/libs/util.py"), top)
+            .is_none());
+        assert!(config
+            .match_venv_rule(Path::new("/elsewhere/services/a.py"), top)
+            .is_none());
+    }
+
+    #[test]
+    fn parse_backend_rules() {
+        let config: Config = toml::from_str(
+            r#"
+            [[backend_rules]]
+            venv = "packages/rust-*/.venv"
+            backend = "ty"
+
+            [[backend_rules]]
+            venv = "/home/*/.cache/pypoetry/virtualenvs/*"
+            backend = "pyrefly"
+            "#,
+        )
+        .unwrap();
+
+        let top = Some(Path::new("/repo"));
+        let backend_of = |venv: &str| {
+            config
+                .match_backend_rule(Path::new(venv), top)
+                .map(|r| r.backend)
+        };
+        assert_eq!(
+            backend_of("/repo/packages/rust-ext/.venv"),
+            Some(BackendKind::Ty)
+        );
+        assert_eq!(
+            backend_of("/home/u/.cache/pypoetry/virtualenvs/app-AbCd-py3.12"),
+            Some(BackendKind::Pyrefly)
+        );
+        assert_eq!(backend_of("/repo/services/legacy/.venv"), None);
+
+        let result: Result<Config, _> =
+            toml::from_str("[[backend_rules]]\nvenv = \"x\"\nbackend = \"jedi\"\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn invalid_glob_is_rejected() {
+        let result: Result<Config, _> = toml::from_str(
+            r#"
+            [[venv_rules]]
+            pattern = "services/[*"
+            venv = "x"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn load_missing_file_yields_default() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = Config::load(Some(temp.path())).unwrap();
+        assert!(config.venv_rules.is_empty());
+        assert_eq!(config.venv_dirs, vec![DEFAULT_VENV_DIR.to_string()]);
+    }
+
+    #[test]
+    fn activated_env_mode_from_file() {
+        let config: Config = toml::from_str(r#"activated_env = "catch-all""#).unwrap();
+        assert_eq!(config.activated_env, ActivatedEnvMode::CatchAll);
+        assert_eq!(Config::default().activated_env, ActivatedEnvMode::Ignore);
+    }
+
+    #[test]
+    fn venv_dirs_override_and_validation() {
+        let config: Config = toml::from_str(r#"venv_dirs = ["venv", "env"]"#).unwrap();
+        assert_eq!(
+            config.venv_dirs,
+            vec!["venv".to_string(), "env".to_string()]
+        );
+
+        let overrides = ConfigOverrides {
+            venv_dirs: Some(vec![".virtualenv".to_string()]),
+            ..Default::default()
+        };
+        let config = config.with_overrides(&overrides).unwrap();
+        assert_eq!(config.venv_dirs, vec![".virtualenv".to_string()]);
+
+        for bad in [vec![], vec!["a/b".to_string()], vec!["..".to_string()]] {
+            let overrides = ConfigOverrides {
+                venv_dirs: Some(bad),
+                ..Default::default()
+            };
+            assert!(Config::default().with_overrides(&overrides).is_err());
+        }
+        for bad in [vec![], vec!["bin/python".to_string()]] {
+            let overrides = ConfigOverrides {
+                venv_markers: Some(bad),
+                ..Default::default()
+            };
+            assert!(Config::default().with_overrides(&overrides).is_err());
+        }
+    }
+
+    #[test]
+    fn backend_tool_dir_override_is_relative_to_cwd() {
+        let overrides = ConfigOverrides {
+            backend_tool_dir: Some(PathBuf::from("tools")),
+            ..Default::default()
+        };
+        let config = Config::default().with_overrides(&overrides).unwrap();
+        assert_eq!(
+            config.backend_tool_dir,
+            Some(std::env::current_dir().unwrap().join("tools"))
+        );
+
+        let overrides = ConfigOverrides {
+            backend_tool_dir: Some(PathBuf::from("/opt/tools")),
+            ..Default::default()
+        };
+        let config = Config::default().with_overrides(&overrides).unwrap();
+        assert_eq!(config.backend_tool_dir, Some(PathBuf::from("/opt/tools")));
+    }
+
+    #[test]
+    fn request_timeout_per_method() {
+        let config: Config = toml::from_str(
+            r#"
+request_timeout_secs = 30
+
+[request_timeouts]
+"workspace/symbol" = 120
+"textDocument/references" = 0
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.request_timeout("textDocument/hover"),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            config.request_timeout("workspace/symbol"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(config.request_timeout("textDocument/references"), None);
+
+        let overrides = ConfigOverrides {
+            request_timeout_secs: Some(0),
+            ..Default::default()
+        };
+        let config = config.with_overrides(&overrides).unwrap();
+        assert_eq!(config.request_timeout("textDocument/hover"), None);
+        assert_eq!(
+            config.request_timeout("workspace/symbol"),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn pinned_venvs_resolve_against_toplevel() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp.path().join(CONFIG_FILE),
+            r#"pinned_venvs = ["services/api/.venv", "/opt/envs/shared"]"#,
+        )
+        .unwrap();
+        let config = Config::load(Some(temp.path())).unwrap();
+        assert_eq!(
+            config.pinned_venvs,
+            vec![
+                temp.path().join("services/api/.venv"),
+                PathBuf::from("/opt/envs/shared")
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_paths_resolve_against_toplevel() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp.path().join(CONFIG_FILE),
+            r#"shared_paths = ["libs", "conftest.py"]"#,
+        )
+        .unwrap();
+        let config = Config::load(Some(temp.path())).unwrap();
+        assert!(config.is_shared_path(&temp.path().join("libs/common/util.py")));
+        assert!(config.is_shared_path(&temp.path().join("conftest.py")));
+        assert!(!config.is_shared_path(&temp.path().join("libs-old/util.py")));
+        assert!(!config.is_shared_path(&temp.path().join("services/api/app.py")));
+    }
+}
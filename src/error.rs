@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -19,6 +20,9 @@ pub enum ProxyError {
 
     #[error("Venv error: {0}")]
     Venv(#[from] VenvError),
+
+    #[error("Config error: {0}")]
+    Config(#[from] ConfigError),
 }
 
 #[derive(Error, Debug)]
@@ -34,6 +38,25 @@ pub enum BackendError {
 
     #[error("Initialize response error: {0}")]
     InitializeResponseError(String),
+
+    #[error("Backend '{0}' has no command: set `command` in [backends.{0}] in .typemux.toml")]
+    MissingCommand(&'static str),
+
+    #[error("Executable '{program}' for backend '{backend}' not found")]
+    ExecutableNotFound {
+        backend: &'static str,
+        program: String,
+    },
+
+    #[error("Backend crashed {crashes} times within {window_secs}s; not restarting it until the crash window passes")]
+    Quarantined { crashes: usize, window_secs: u64 },
+
+    #[error("Backend '{backend}' must be installed in the venv, but {}/bin/{program} does not exist", venv.display())]
+    NotInVenv {
+        backend: &'static str,
+        program: String,
+        venv: PathBuf,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -55,4 +78,28 @@ pub enum FramingError {
 pub enum VenvError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+
+    #[error("venv rule '{pattern}' points at missing environment {}", venv.display())]
+    MissingRuleTarget { pattern: String, venv: PathBuf },
+
+    #[error("venv {} was removed", .0.display())]
+    Removed(PathBuf),
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid config: {0}")]
+    Invalid(String),
 }
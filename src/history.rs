@@ -0,0 +1,297 @@
+//! Venv usage history persisted across sessions.
+//!
+//! One JSON file per git toplevel under `$XDG_STATE_HOME/typemux-cc/history/`
+//! (default `~/.local/state`), named by a hash of the toplevel path. The file is
+//! advisory: read and write failures are logged and otherwise ignored.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Minimum time between writes of the history file (changes are flushed on shutdown)
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Weight of the newest sample in the warmup duration average
+const WARMUP_SMOOTHING: f64 = 0.3;
+
+/// A tuned warmup timeout is the average observed warmup times this factor,
+/// clamped to the bounds below
+const WARMUP_TIMEOUT_FACTOR: f64 = 1.5;
+const MIN_TUNED_WARMUP_TIMEOUT: Duration = Duration::from_millis(500);
+const MAX_TUNED_WARMUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Usage of a single venv
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VenvUsage {
+    /// Last time a document of this venv was opened (Unix seconds)
+    pub last_used: u64,
+    /// Documents opened in this venv, over all sessions
+    pub use_count: u64,
+    /// Moving average of the time from spawn to the backend's `$/progress` end
+    pub warmup_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    /// Git toplevel the file belongs to (informational)
+    toplevel: PathBuf,
+    venvs: BTreeMap<PathBuf, VenvUsage>,
+}
+
+/// Venv usage history of one git toplevel
+#[derive(Debug, Default)]
+pub struct VenvHistory {
+    /// None when there is no toplevel or no state directory (history kept in memory only)
+    path: Option<PathBuf>,
+    file: HistoryFile,
+    dirty: bool,
+    last_flush: Option<SystemTime>,
+}
+
+impl VenvHistory {
+    /// Load the history of `toplevel` (empty if missing or unreadable)
+    pub fn load(toplevel: Option<&Path>) -> Self {
+        let Some(toplevel) = toplevel else {
+            return Self::default();
+        };
+        let Some(path) = state_dir().map(|dir| history_path(&dir, toplevel)) else {
+            tracing::debug!("No state directory, venv history is not persisted");
+            return Self::default();
+        };
+
+        let file = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<HistoryFile>(&content) {
+                Ok(file) => file,
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "Ignoring unreadable venv history");
+                    HistoryFile::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HistoryFile::default(),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to read venv history");
+                HistoryFile::default()
+            }
+        };
+        tracing::debug!(path = %path.display(), venvs = file.venvs.len(), "Loaded venv history");
+
+        Self {
+            path: Some(path),
+            file: HistoryFile {
+                toplevel: toplevel.to_path_buf(),
+                ..file
+            },
+            dirty: false,
+            last_flush: None,
+        }
+    }
+
+    /// Record that a document of `venv_path` was opened
+    pub fn touch(&mut self, venv_path: &Path) {
+        let usage = self.file.venvs.entry(venv_path.to_path_buf()).or_default();
+        usage.last_used = unix_now();
+        usage.use_count += 1;
+        self.dirty = true;
+    }
+
+    /// Record how long a backend for `venv_path` took to finish indexing
+    pub fn record_warmup(&mut self, venv_path: &Path, warmup: Duration) {
+        let usage = self.file.venvs.entry(venv_path.to_path_buf()).or_default();
+        let sample = warmup.as_millis() as f64;
+        let average = match usage.warmup_ms {
+            Some(previous) => {
+                previous as f64 * (1.0 - WARMUP_SMOOTHING) + sample * WARMUP_SMOOTHING
+            }
+            None => sample,
+        };
+        usage.warmup_ms = Some(average.round() as u64);
+        self.dirty = true;
+    }
+
+    /// Warmup timeout tuned to `venv_path`'s observed warmups (None if never observed)
+    pub fn warmup_timeout(&self, venv_path: &Path) -> Option<Duration> {
+        let warmup_ms = self.file.venvs.get(venv_path)?.warmup_ms?;
+        let timeout = Duration::from_millis((warmup_ms as f64 * WARMUP_TIMEOUT_FACTOR) as u64);
+        Some(timeout.clamp(MIN_TUNED_WARMUP_TIMEOUT, MAX_TUNED_WARMUP_TIMEOUT))
+    }
+
+    /// `candidates` that have been used before, in pre-warm order
+    pub fn prewarm_order(&self, candidates: &[PathBuf]) -> Vec<PathBuf> {
+        prewarm_order(&self.file.venvs, candidates, unix_now())
+    }
+
+    /// Write pending changes, at most once per `FLUSH_INTERVAL`
+    pub fn flush_if_due(&mut self) {
+        let due = self.last_flush.map_or(true, |last| {
+            last.elapsed()
+                .map_or(true, |elapsed| elapsed >= FLUSH_INTERVAL)
+        });
+        if due {
+            self.flush();
+        }
+    }
+
+    /// Write pending changes now
+    pub fn flush(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        self.last_flush = Some(SystemTime::now());
+
+        let result = serde_json::to_string_pretty(&self.file)
+            .map_err(std::io::Error::other)
+            .and_then(|content| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                // Write-then-rename so concurrent sessions never see a partial file
+                let tmp = path.with_extension(format!("json.{}", std::process::id()));
+                std::fs::write(&tmp, content)?;
+                std::fs::rename(&tmp, path)
+            });
+        match result {
+            Ok(()) => tracing::debug!(path = %path.display(), "Saved venv history"),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to save venv history")
+            }
+        }
+    }
+}
+
+/// `$XDG_STATE_HOME/typemux-cc`, falling back to `~/.local/state/typemux-cc`
+fn state_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .filter(|h| !h.is_empty())
+                .map(|home| PathBuf::from(home).join(".local/state"))
+        })?;
+    Some(base.join("typemux-cc"))
+}
+
+/// History file of `toplevel`: `<state dir>/history/<first 16 hex digits of sha256(path)>.json`
+fn history_path(state_dir: &Path, toplevel: &Path) -> PathBuf {
+    let digest = Sha256::digest(toplevel.as_os_str().as_encoded_bytes());
+    let hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    state_dir.join("history").join(format!("{}.json", hash))
+}
+
+/// Order known `candidates` by use count, decayed by days since last use
+/// (`use_count / (1 + days)`), so a venv used daily beats one used heavily a month ago
+fn prewarm_order(
+    venvs: &BTreeMap<PathBuf, VenvUsage>,
+    candidates: &[PathBuf],
+    now: u64,
+) -> Vec<PathBuf> {
+    let mut scored: Vec<(&PathBuf, f64)> = candidates
+        .iter()
+        .filter_map(|venv| {
+            let usage = venvs.get(venv)?;
+            let days = now.saturating_sub(usage.last_used) as f64 / 86_400.0;
+            Some((venv, usage.use_count.max(1) as f64 / (1.0 + days)))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    scored.into_iter().map(|(venv, _)| venv.clone()).collect()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_path_is_stable_per_toplevel() {
+        let dir = Path::new("/state/typemux-cc");
+        let a = history_path(dir, Path::new("/work/repo"));
+        assert_eq!(a, history_path(dir, Path::new("/work/repo")));
+        assert_ne!(a, history_path(dir, Path::new("/work/other")));
+        assert!(a.starts_with("/state/typemux-cc/history"));
+        assert_eq!(a.extension().unwrap(), "json");
+    }
+
+    #[test]
+    fn prewarm_order_decays_use_count_by_age() {
+        const DAY: u64 = 86_400;
+        let now = 100 * DAY;
+        let usage = |days_ago: u64, use_count: u64| VenvUsage {
+            last_used: now - days_ago * DAY,
+            use_count,
+            warmup_ms: None,
+        };
+        let venvs = BTreeMap::from([
+            (PathBuf::from("/r/daily/.venv"), usage(0, 10)),
+            (PathBuf::from("/r/old-heavy/.venv"), usage(30, 100)),
+            (PathBuf::from("/r/yesterday/.venv"), usage(1, 2)),
+        ]);
+        let candidates = [
+            PathBuf::from("/r/yesterday/.venv"),
+            PathBuf::from("/r/old-heavy/.venv"),
+            PathBuf::from("/r/daily/.venv"),
+            PathBuf::from("/r/never-used/.venv"),
+        ];
+        assert_eq!(
+            prewarm_order(&venvs, &candidates, now),
+            vec![
+                PathBuf::from("/r/daily/.venv"),
+                PathBuf::from("/r/old-heavy/.venv"),
+                PathBuf::from("/r/yesterday/.venv"),
+            ]
+        );
+    }
+
+    #[test]
+    fn warmup_timeout_follows_observed_warmups() {
+        let mut history = VenvHistory::default();
+        let venv = Path::new("/r/a/.venv");
+        assert_eq!(history.warmup_timeout(venv), None);
+
+        history.record_warmup(venv, Duration::from_secs(4));
+        assert_eq!(history.warmup_timeout(venv), Some(Duration::from_secs(6)));
+
+        // Moving average: 4s * 0.7 + 14s * 0.3 = 7s
+        history.record_warmup(venv, Duration::from_secs(14));
+        assert_eq!(
+            history.warmup_timeout(venv),
+            Some(Duration::from_millis(10_500))
+        );
+
+        history.record_warmup(Path::new("/r/fast/.venv"), Duration::from_millis(10));
+        assert_eq!(
+            history.warmup_timeout(Path::new("/r/fast/.venv")),
+            Some(MIN_TUNED_WARMUP_TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn usage_from_older_files_defaults_new_fields() {
+        let file: HistoryFile = serde_json::from_str(
+            r#"{"toplevel": "/r", "venvs": {"/r/a/.venv": {"last_used": 5}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            file.venvs[Path::new("/r/a/.venv")],
+            VenvUsage {
+                last_used: 5,
+                use_count: 0,
+                warmup_ms: None
+            }
+        );
+    }
+}
@@ -1,15 +1,19 @@
 mod backend;
 mod backend_pool;
+mod config;
 mod error;
 mod framing;
+mod history;
 mod message;
 mod proxy;
 mod state;
 mod text_edit;
 mod venv;
+mod watcher;
 
 use backend::BackendKind;
 use clap::Parser;
+use config::{ActivatedEnvMode, ConfigOverrides};
 use proxy::LspProxy;
 use std::path::PathBuf;
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
@@ -33,7 +37,7 @@ struct Args {
     #[arg(long, env = "TYPEMUX_CC_BACKEND_TTL", default_value = "1800")]
     backend_ttl: u64,
 
-    /// LSP backend to use: pyright, ty, or pyrefly
+    /// Default LSP backend: pyright, ty, pyrefly, or custom (command from .typemux.toml)
     /// Can also be set via TYPEMUX_CC_BACKEND environment variable
     #[arg(
         long,
@@ -42,6 +46,133 @@ struct Args {
         value_enum
     )]
     backend: BackendKind,
+
+    /// Candidate venv directory names, checked in order at each directory level
+    /// (comma-separated, default: .venv). Overrides `venv_dirs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_VENV_DIRS environment variable
+    #[arg(long, env = "TYPEMUX_CC_VENV_DIRS", value_delimiter = ',')]
+    venv_dirs: Option<Vec<String>>,
+
+    /// Files marking a candidate directory as a venv, any of them
+    /// (comma-separated, default: pyvenv.cfg). Overrides `venv_markers` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_VENV_MARKERS environment variable
+    #[arg(long, env = "TYPEMUX_CC_VENV_MARKERS", value_delimiter = ',')]
+    venv_markers: Option<Vec<String>>,
+
+    /// Use the env activated in the launching shell (VIRTUAL_ENV / CONDA_PREFIX):
+    /// ignore, fallback (startup backend), or catch-all (also for files with no venv).
+    /// Overrides `activated_env` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_ACTIVATED_ENV environment variable
+    #[arg(long, env = "TYPEMUX_CC_ACTIVATED_ENV", value_enum)]
+    activated_env: Option<ActivatedEnvMode>,
+
+    /// Route uv workspace members to the workspace root venv (default: true).
+    /// Overrides `prefer_uv_workspace_venv` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_PREFER_UV_WORKSPACE_VENV environment variable
+    #[arg(long, env = "TYPEMUX_CC_PREFER_UV_WORKSPACE_VENV", value_name = "BOOL")]
+    prefer_uv_workspace_venv: Option<bool>,
+
+    /// Watch for venvs being created or removed and migrate open documents (default: true).
+    /// Overrides `watch_venvs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_WATCH_VENVS environment variable
+    #[arg(long, env = "TYPEMUX_CC_WATCH_VENVS", value_name = "BOOL")]
+    watch_venvs: Option<bool>,
+
+    /// Restart a backend when its venv's installed packages change (default: true).
+    /// Overrides `restart_on_package_change` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_RESTART_ON_PACKAGE_CHANGE environment variable
+    #[arg(
+        long,
+        env = "TYPEMUX_CC_RESTART_ON_PACKAGE_CHANGE",
+        value_name = "BOOL"
+    )]
+    restart_on_package_change: Option<bool>,
+
+    /// Directory searched for backend executables after the venv's bin/ and before PATH.
+    /// Overrides `backend_tool_dir` in .typemux.toml (relative to the working directory).
+    /// Can also be set via TYPEMUX_CC_BACKEND_TOOL_DIR environment variable
+    #[arg(long, env = "TYPEMUX_CC_BACKEND_TOOL_DIR")]
+    backend_tool_dir: Option<PathBuf>,
+
+    /// Only use backend executables installed in the target venv; fail otherwise (default: false).
+    /// Overrides `require_venv_backend` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_REQUIRE_VENV_BACKEND environment variable
+    #[arg(long, env = "TYPEMUX_CC_REQUIRE_VENV_BACKEND", value_name = "BOOL")]
+    require_venv_backend: Option<bool>,
+
+    /// Crashes within the crash window after which a venv's backend is no longer
+    /// respawned (default: 5, minimum: 1). Overrides `crash_limit` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_CRASH_LIMIT environment variable
+    #[arg(long, env = "TYPEMUX_CC_CRASH_LIMIT")]
+    crash_limit: Option<u32>,
+
+    /// Window for counting backend crashes, in seconds (default: 300).
+    /// Overrides `crash_window_secs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_CRASH_WINDOW environment variable
+    #[arg(long, env = "TYPEMUX_CC_CRASH_WINDOW")]
+    crash_window: Option<u64>,
+
+    /// Seconds a backend may leave a request unanswered before it is considered hung
+    /// and restarted (default: 120, 0 = disabled). Overrides `hang_timeout_secs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_HANG_TIMEOUT environment variable
+    #[arg(long, env = "TYPEMUX_CC_HANG_TIMEOUT")]
+    hang_timeout: Option<u64>,
+
+    /// Seconds between liveness probes sent to idle backends (default: 0 = disabled).
+    /// Overrides `health_probe_interval_secs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_HEALTH_PROBE_INTERVAL environment variable
+    #[arg(long, env = "TYPEMUX_CC_HEALTH_PROBE_INTERVAL")]
+    health_probe_interval: Option<u64>,
+
+    /// Seconds a request may wait for a backend response before it fails with a timeout error
+    /// (default: 60, 0 = disabled). Overrides `request_timeout_secs` in .typemux.toml;
+    /// per-method timeouts in `[request_timeouts]` still apply.
+    /// Can also be set via TYPEMUX_CC_REQUEST_TIMEOUT environment variable
+    #[arg(long, env = "TYPEMUX_CC_REQUEST_TIMEOUT")]
+    request_timeout: Option<u64>,
+
+    /// Seconds to wait for all backends' answers to `workspace/symbol` when several are
+    /// active, before returning partial results (default: 5, 0 = wait for all).
+    /// Overrides `fanout_timeout_secs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_FANOUT_TIMEOUT environment variable
+    #[arg(long, env = "TYPEMUX_CC_FANOUT_TIMEOUT")]
+    fanout_timeout: Option<u64>,
+
+    /// Total resident memory of all backends in MiB; LRU backends are evicted above it
+    /// (default: 0 = no budget, Linux only). Overrides `memory_budget_mb` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_MEMORY_BUDGET environment variable
+    #[arg(long, env = "TYPEMUX_CC_MEMORY_BUDGET")]
+    memory_budget: Option<u64>,
+
+    /// Seconds a backend may be idle before its process is suspended until the next
+    /// request (default: 0 = never; Unix only). Overrides `suspend_ttl_secs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_SUSPEND_TTL environment variable
+    #[arg(long, env = "TYPEMUX_CC_SUSPEND_TTL")]
+    suspend_ttl: Option<u64>,
+
+    /// Venvs whose backends are never evicted and don't count toward --max-backends
+    /// (comma-separated). Overrides `pinned_venvs` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_PINNED_VENVS environment variable
+    #[arg(long, env = "TYPEMUX_CC_PINNED_VENVS", value_delimiter = ',')]
+    pinned_venvs: Option<Vec<PathBuf>>,
+
+    /// Files and directories shared by several projects, whose watched-file changes go to
+    /// every backend (comma-separated). Overrides `shared_paths` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_SHARED_PATHS environment variable
+    #[arg(long, env = "TYPEMUX_CC_SHARED_PATHS", value_delimiter = ',')]
+    shared_paths: Option<Vec<PathBuf>>,
+
+    /// Pre-warm backends for recently used venvs under the git toplevel after startup
+    /// (default: false). Overrides `prewarm` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_PREWARM environment variable
+    #[arg(long, env = "TYPEMUX_CC_PREWARM", value_name = "BOOL")]
+    prewarm: Option<bool>,
+
+    /// Maximum number of backends started by pre-warming (default: 2).
+    /// Overrides `prewarm_budget` in .typemux.toml.
+    /// Can also be set via TYPEMUX_CC_PREWARM_BUDGET environment variable
+    #[arg(long, env = "TYPEMUX_CC_PREWARM_BUDGET")]
+    prewarm_budget: Option<usize>,
 }
 
 #[tokio::main]
@@ -114,8 +245,43 @@ async fn main() -> anyhow::Result<()> {
         Some(std::time::Duration::from_secs(args.backend_ttl))
     };
 
+    let config_overrides = ConfigOverrides {
+        venv_dirs: args.venv_dirs,
+        venv_markers: args.venv_markers,
+        activated_env: args.activated_env,
+        prefer_uv_workspace_venv: args.prefer_uv_workspace_venv,
+        watch_venvs: args.watch_venvs,
+        restart_on_package_change: args.restart_on_package_change,
+        backend_tool_dir: args.backend_tool_dir,
+        require_venv_backend: args.require_venv_backend,
+        crash_limit: args.crash_limit,
+        crash_window_secs: args.crash_window,
+        hang_timeout_secs: args.hang_timeout,
+        health_probe_interval_secs: args.health_probe_interval,
+        request_timeout_secs: args.request_timeout,
+        fanout_timeout_secs: args.fanout_timeout,
+        memory_budget_mb: args.memory_budget,
+        suspend_ttl_secs: args.suspend_ttl,
+        // Relative to the working directory, like other CLI paths
+        pinned_venvs: args.pinned_venvs.map(|venvs| {
+            let cwd = std::env::current_dir().unwrap_or_default();
+            venvs.iter().map(|venv| cwd.join(venv)).collect()
+        }),
+        shared_paths: args.shared_paths.map(|paths| {
+            let cwd = std::env::current_dir().unwrap_or_default();
+            paths.iter().map(|path| cwd.join(path)).collect()
+        }),
+        prewarm: args.prewarm,
+        prewarm_budget: args.prewarm_budget,
+    };
+
     // Start proxy
-    let mut proxy = LspProxy::new(args.backend, args.max_backends as usize, backend_ttl);
+    let mut proxy = LspProxy::new(
+        args.backend,
+        args.max_backends as usize,
+        backend_ttl,
+        config_overrides,
+    );
     proxy.run().await?;
 
     Ok(())
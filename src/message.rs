@@ -32,6 +32,9 @@ pub struct RpcError {
     pub data: Option<Value>,
 }
 
+/// LSP `RequestCancelled` error code
+pub const REQUEST_CANCELLED: i64 = -32800;
+
 impl RpcMessage {
     /// Check if this is a request
     pub fn is_request(&self) -> bool {
@@ -68,4 +71,49 @@ impl RpcMessage {
             }),
         }
     }
+
+    /// Create an internal error response for the request with `id`
+    /// (for requests known by id only, e.g. after rewriting it)
+    pub fn internal_error_response(id: RpcId, message: &str) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id),
+            method: None,
+            params: None,
+            result: None,
+            error: Some(RpcError {
+                code: -32603,
+                message: message.to_string(),
+                data: None,
+            }),
+        }
+    }
+
+    /// Create a `RequestCancelled` error response for the request with `id`
+    pub fn cancelled_response(id: RpcId, message: &str) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id),
+            method: None,
+            params: None,
+            result: None,
+            error: Some(RpcError {
+                code: REQUEST_CANCELLED,
+                message: message.to_string(),
+                data: None,
+            }),
+        }
+    }
+
+    /// Create a `$/cancelRequest` notification for the request with `id`
+    pub fn cancel_notification(id: &RpcId) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: Some("$/cancelRequest".to_string()),
+            params: Some(serde_json::json!({ "id": id })),
+            result: None,
+            error: None,
+        }
+    }
 }
@@ -85,26 +85,52 @@ impl super::LspProxy {
                     return Ok(());
                 }
 
-                // Handle response: check pending + stale check
+                // Handle response: map the backend request ID back to the client's
                 if msg.is_response() {
-                    if let Some(id) = &msg.id {
-                        if let Some(pending) = self.state.pending_requests.get(id) {
-                            if pending.backend_session != session || pending.venv_path != venv_path
-                            {
-                                tracing::warn!(
-                                    id = ?id,
-                                    pending_session = pending.backend_session,
-                                    pending_venv = %pending.venv_path.display(),
-                                    msg_session = session,
-                                    msg_venv = %venv_path.display(),
-                                    "Discarding stale response from old backend session"
-                                );
-                                self.state.pending_requests.remove(id);
-                                return Ok(());
-                            }
+                    let Some(id) = msg.id.clone() else {
+                        client_writer.write_message(&msg).await?;
+                        return Ok(());
+                    };
+                    if self.state.late_requests.remove(&id).is_some() {
+                        tracing::debug!(
+                            id = ?id,
+                            venv = %venv_path.display(),
+                            "Dropping late response to a request already answered"
+                        );
+                        return Ok(());
+                    }
+                    if let Some(pending) = self.state.remove_pending(&id) {
+                        if pending.backend_session != session || pending.venv_path != venv_path {
+                            tracing::warn!(
+                                id = ?pending.client_id,
+                                pending_session = pending.backend_session,
+                                pending_venv = %pending.venv_path.display(),
+                                msg_session = session,
+                                msg_venv = %venv_path.display(),
+                                "Discarding stale response from old backend session"
+                            );
+                            return Ok(());
                         }
-                        self.state.pending_requests.remove(id);
+                        client_writer
+                            .write_message(&pending.client_response(msg))
+                            .await?;
+                        return Ok(());
+                    }
+
+                    // Fan-out answers are merged before reaching the client
+                    if self.fanout.is_sub_request(&id) {
+                        return self.handle_fanout_response(msg, client_writer).await;
+                    }
+
+                    // Liveness probe answers are for the proxy only
+                    if !self.health.probe_answered(&venv_path, &id) {
+                        tracing::debug!(
+                            id = ?id,
+                            venv = %venv_path.display(),
+                            "Dropping response to unknown request"
+                        );
                     }
+                    return Ok(());
                 }
 
                 // Detect $/progress end → transition warming backend to ready
@@ -112,9 +138,14 @@ impl super::LspProxy {
                     if let Some(method) = msg.method_name() {
                         if method == "$/progress" && is_progress_end(&msg) {
                             if let Some(inst) = self.state.pool.get_mut(&venv_path) {
+                                let warmup = inst.finish_indexing();
+                                if let Some(warmup) = warmup {
+                                    self.history.record_warmup(&venv_path, warmup);
+                                }
                                 if inst.is_warming() {
                                     tracing::info!(
                                         venv = %venv_path.display(),
+                                        warmup_ms = warmup.map(|w| w.as_millis() as u64),
                                         "Backend warmup complete (reason: progress), transitioning to Ready"
                                     );
                                     let queued = inst.mark_ready();
@@ -127,6 +158,12 @@ impl super::LspProxy {
                                         )
                                         .await?;
                                     }
+                                } else if let Some(warmup) = warmup {
+                                    tracing::info!(
+                                        venv = %venv_path.display(),
+                                        warmup_ms = warmup.as_millis() as u64,
+                                        "Backend finished indexing after the warmup timeout"
+                                    );
                                 }
                             }
                         }
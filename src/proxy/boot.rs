@@ -0,0 +1,466 @@
+use super::client_dispatch::INDEX_DEPENDENT_METHODS;
+use crate::backend::{BackendKind, LspBackend};
+use crate::backend_pool::shutdown_backend_instance;
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::{RpcId, RpcMessage};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Result of a background boot (spawn + initialize handshake)
+pub(crate) struct BootResult {
+    pub venv_path: PathBuf,
+    pub session: u64,
+    pub kind: BackendKind,
+    pub result: Result<LspBackend, ProxyError>,
+}
+
+/// A backend that is being spawned and initialized off the main loop
+pub(crate) struct BootingBackend {
+    /// Session the backend will have once it joins the pool
+    pub session: u64,
+    pub kind: BackendKind,
+    /// Client messages received while booting, forwarded once it is ready
+    pub queue: Vec<RpcMessage>,
+    task: JoinHandle<()>,
+}
+
+/// Backends currently booting, keyed by venv path.
+/// A venv has at most one boot in flight; restarting a boot gives it a new session.
+pub(crate) struct BootState {
+    booting: HashMap<PathBuf, BootingBackend>,
+    tx: mpsc::Sender<BootResult>,
+    pub rx: mpsc::Receiver<BootResult>,
+}
+
+impl Default for BootState {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel(16);
+        Self {
+            booting: HashMap::new(),
+            tx,
+            rx,
+        }
+    }
+}
+
+impl BootState {
+    pub fn contains(&self, venv_path: &Path) -> bool {
+        self.booting.contains_key(venv_path)
+    }
+
+    pub fn get(&self, venv_path: &Path) -> Option<&BootingBackend> {
+        self.booting.get(venv_path)
+    }
+
+    pub fn get_mut(&mut self, venv_path: &Path) -> Option<&mut BootingBackend> {
+        self.booting.get_mut(venv_path)
+    }
+
+    /// The only booting venv, if exactly one backend is booting
+    pub fn single(&self) -> Option<PathBuf> {
+        match self.booting.len() {
+            1 => self.booting.keys().next().cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn venvs(&self) -> Vec<PathBuf> {
+        self.booting.keys().cloned().collect()
+    }
+
+    /// Remove a queued request by its JSON-RPC id (for $/cancelRequest handling)
+    pub fn cancel_queued_request(&mut self, venv_path: &Path, id: &RpcId) -> Option<RpcMessage> {
+        let boot = self.booting.get_mut(venv_path)?;
+        let pos = boot
+            .queue
+            .iter()
+            .position(|msg| msg.id.as_ref() == Some(id))?;
+        Some(boot.queue.remove(pos))
+    }
+
+    /// Abort a boot, returning its queued messages
+    pub fn abort(&mut self, venv_path: &Path) -> Option<BootingBackend> {
+        let boot = self.booting.remove(venv_path)?;
+        // Dropping the half-initialized backend kills its process
+        boot.task.abort();
+        Some(boot)
+    }
+
+    /// Abort every boot (proxy shutdown)
+    pub fn abort_all(&mut self) {
+        for (_, boot) in self.booting.drain() {
+            boot.task.abort();
+        }
+    }
+}
+
+impl super::LspProxy {
+    /// Booting backends that will take a pool slot (pinned ones don't)
+    pub(crate) fn unpinned_booting_count(&self) -> usize {
+        self.boot
+            .venvs()
+            .iter()
+            .filter(|venv| !self.state.pool.is_pinned(venv))
+            .count()
+    }
+
+    /// Start spawning and initializing a backend for `venv_path` in the background.
+    ///
+    /// The result arrives on `BootState::rx` and is handled by `handle_boot_complete`,
+    /// which restores the venv's documents and forwards messages queued meanwhile.
+    /// If the venv already has a backend in the pool, the new one replaces it on
+    /// completion (restart); until then the old one keeps serving requests.
+    ///
+    /// `restart` re-boots a venv whose boot is already in flight (e.g. its packages
+    /// changed mid-boot); otherwise an in-flight boot of the same `kind` is reused
+    /// (one of another kind is restarted with `kind`).
+    pub(crate) async fn start_backend_boot(
+        &mut self,
+        venv_path: &Path,
+        kind: BackendKind,
+        restart: bool,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let booting_kind = self.boot.get(venv_path).map(|boot| boot.kind);
+        if booting_kind == Some(kind) && !restart {
+            return Ok(());
+        }
+
+        if !self.state.pool.contains(venv_path) {
+            self.check_quarantine(venv_path)?;
+        }
+
+        let command = self.state.config.backend_command(kind)?;
+        let init_params = self.cached_init_params()?;
+
+        let queue = match self.boot.abort(venv_path) {
+            Some(previous) => {
+                tracing::info!(
+                    session = previous.session,
+                    venv = %venv_path.display(),
+                    "Restarting backend boot"
+                );
+                previous.queue
+            }
+            None => {
+                // Replacements don't grow the pool, and pinned backends don't count
+                let pool = &self.state.pool;
+                let needs_room = !pool.contains(venv_path)
+                    && !pool.is_pinned(venv_path)
+                    && pool.is_full(self.unpinned_booting_count());
+                if needs_room {
+                    self.evict_lru_backend(client_writer).await?;
+                }
+                Vec::new()
+            }
+        };
+        // A fresh session even when re-booting, so a result the aborted task already
+        // sent is recognized as stale
+        let session = self.state.pool.next_session_id();
+        for msg in &queue {
+            if let Some(pending) = msg
+                .id
+                .as_ref()
+                .and_then(|id| self.state.pending_requests.get_mut(id))
+            {
+                pending.backend_session = session;
+            }
+        }
+
+        tracing::info!(
+            session = session,
+            venv = %venv_path.display(),
+            backend = kind.display_name(),
+            "Booting backend in the background"
+        );
+
+        let tx = self.boot.tx.clone();
+        let venv = venv_path.to_path_buf();
+        let task = tokio::spawn(async move {
+            let result = async {
+                let mut backend = LspBackend::spawn(kind, &command, Some(&venv)).await?;
+                let init_response = super::initialization::perform_initialize_handshake(
+                    &mut backend,
+                    init_params,
+                    &venv,
+                )
+                .await?;
+                crate::backend::log_backend_executable(
+                    kind,
+                    backend.executable(),
+                    &venv,
+                    session,
+                    &init_response,
+                );
+                Ok(backend)
+            }
+            .await;
+
+            // The receiver only goes away when the proxy exits
+            let _ = tx
+                .send(BootResult {
+                    venv_path: venv,
+                    session,
+                    kind,
+                    result,
+                })
+                .await;
+        });
+
+        self.boot.booting.insert(
+            venv_path.to_path_buf(),
+            BootingBackend {
+                session,
+                kind,
+                queue,
+                task,
+            },
+        );
+        Ok(())
+    }
+
+    /// Insert a booted backend into the pool (replacing a running one if any),
+    /// or report the failure to the client and fail the requests queued for it.
+    pub(crate) async fn handle_boot_complete(
+        &mut self,
+        done: BootResult,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let BootResult {
+            venv_path,
+            session,
+            kind,
+            result,
+        } = done;
+
+        // A boot that was aborted or restarted may still deliver its result
+        let current = self
+            .boot
+            .booting
+            .get(&venv_path)
+            .is_some_and(|boot| boot.session == session);
+        if !current {
+            tracing::debug!(
+                session = session,
+                venv = %venv_path.display(),
+                "Discarding result of superseded backend boot"
+            );
+            return Ok(());
+        }
+        let Some(boot) = self.boot.booting.remove(&venv_path) else {
+            return Ok(());
+        };
+
+        let mut backend = match result {
+            Ok(backend) => backend,
+            Err(e) => {
+                if self.state.pool.contains(&venv_path) {
+                    tracing::error!(
+                        venv = %venv_path.display(),
+                        error = ?e,
+                        "Failed to start replacement backend, keeping the old one"
+                    );
+                } else if self.is_recovering_from_crash(&venv_path) {
+                    // A failed respawn counts as another crash (retried with backoff)
+                    tracing::error!(
+                        venv = %venv_path.display(),
+                        error = ?e,
+                        "Failed to respawn crashed backend"
+                    );
+                    self.schedule_crash_respawn(&venv_path, client_writer).await;
+                } else {
+                    tracing::error!(
+                        venv = %venv_path.display(),
+                        error = ?e,
+                        "Failed to create backend"
+                    );
+                    self.notify_backend_error(&venv_path, &e, client_writer)
+                        .await;
+                }
+                self.fail_queued_messages(boot.queue, &e, client_writer)
+                    .await?;
+                return Ok(());
+            }
+        };
+        tracing::info!(session = session, venv = %venv_path.display(), "Backend initialized");
+
+        // Documents are restored from the cache as it is now, so edits made while
+        // booting are included
+        self.restore_documents_to_backend(&mut backend, &venv_path, session, client_writer)
+            .await?;
+        let mut instance = self.build_backend_instance(backend, &venv_path, kind, session);
+
+        if let Some(old) = self.state.pool.remove(&venv_path) {
+            let old_session = old.session;
+            // Backend→client requests of the old session can no longer be answered
+            self.clean_pending_backend_requests(&venv_path, old_session);
+            shutdown_backend_instance(old);
+            self.fanout_backend_gone(&venv_path, old_session, client_writer)
+                .await?;
+
+            // Replay the old session's in-flight requests (its warmup queue included);
+            // requests the client cancelled are answered instead
+            let (replay, cancelled) =
+                self.state
+                    .take_restart_replay(&venv_path, old_session, session);
+            for pending in cancelled {
+                let response = RpcMessage::cancelled_response(
+                    pending.client_id,
+                    "lsp-proxy: request cancelled",
+                );
+                client_writer.write_message(&response).await?;
+            }
+            let replayed = replay.len();
+            self.forward_to_new_backend(&mut instance, replay, "restart", client_writer)
+                .await?;
+
+            tracing::info!(
+                venv = %venv_path.display(),
+                old_session = old_session,
+                new_session = session,
+                replayed = replayed,
+                "Backend restarted"
+            );
+        }
+
+        if !boot.queue.is_empty() {
+            tracing::info!(
+                venv = %venv_path.display(),
+                queued_count = boot.queue.len(),
+                "Backend booted, forwarding queued messages"
+            );
+        }
+        self.forward_to_new_backend(&mut instance, boot.queue, "boot", client_writer)
+            .await?;
+
+        if let Some(displaced) = self.state.pool.insert(instance) {
+            self.shutdown_removed_backend(displaced, client_writer)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send messages to a backend that is not yet in the pool. While it is warming,
+    /// index-dependent requests go to its warmup queue. Requests that cannot be
+    /// written are answered with an error.
+    async fn forward_to_new_backend(
+        &mut self,
+        instance: &mut crate::backend_pool::BackendInstance,
+        messages: Vec<RpcMessage>,
+        phase: &str,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        for msg in messages {
+            let queue = instance.is_warming()
+                && msg
+                    .method_name()
+                    .is_some_and(|m| INDEX_DEPENDENT_METHODS.contains(&m));
+            if queue {
+                instance.warmup_queue.push(msg);
+                continue;
+            }
+            if let Some(pending) = msg
+                .id
+                .as_ref()
+                .and_then(|id| self.state.pending_requests.get_mut(id))
+            {
+                pending.sent_at = Instant::now();
+            }
+            if let Err(e) = instance.send(&msg).await {
+                tracing::error!(
+                    id = ?msg.id,
+                    venv = %instance.venv_path.display(),
+                    error = ?e,
+                    "Failed to forward message to new backend"
+                );
+                if let Some(pending) = msg.id.as_ref().and_then(|id| self.state.remove_pending(id))
+                {
+                    let error_response = RpcMessage::internal_error_response(
+                        pending.client_id,
+                        &format!("lsp-proxy: backend write failed during {}", phase),
+                    );
+                    client_writer.write_message(&error_response).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer requests queued for a backend that failed to boot
+    pub(crate) async fn fail_queued_messages(
+        &mut self,
+        queue: Vec<RpcMessage>,
+        error: &ProxyError,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        for msg in queue {
+            let Some(pending) = msg.id.as_ref().and_then(|id| self.state.remove_pending(id)) else {
+                continue;
+            };
+            let error_response = RpcMessage::internal_error_response(
+                pending.client_id,
+                &format!("lsp-proxy: backend error: {}", error),
+            );
+            client_writer.write_message(&error_response).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: i64) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(RpcId::Number(id)),
+            method: Some("textDocument/hover".to_string()),
+            params: None,
+            result: None,
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_removes_only_the_queued_request() {
+        let venv = PathBuf::from("/a/.venv");
+        let mut boot = BootState::default();
+        boot.booting.insert(
+            venv.clone(),
+            BootingBackend {
+                session: 1,
+                kind: BackendKind::Pyright,
+                queue: vec![request(1), request(2), request(3)],
+                task: tokio::spawn(async {}),
+            },
+        );
+
+        let cancelled = boot.cancel_queued_request(&venv, &RpcId::Number(2));
+        assert_eq!(cancelled.and_then(|m| m.id), Some(RpcId::Number(2)));
+        let queued: Vec<_> = boot
+            .get(&venv)
+            .unwrap()
+            .queue
+            .iter()
+            .map(|m| m.id.clone())
+            .collect();
+        assert_eq!(queued, vec![Some(RpcId::Number(1)), Some(RpcId::Number(3))]);
+
+        // Already cancelled, never queued, or not booting
+        assert!(boot
+            .cancel_queued_request(&venv, &RpcId::Number(2))
+            .is_none());
+        assert!(boot
+            .cancel_queued_request(&venv, &RpcId::Number(9))
+            .is_none());
+        assert!(boot
+            .cancel_queued_request(Path::new("/b/.venv"), &RpcId::Number(1))
+            .is_none());
+        assert_eq!(boot.get(&venv).unwrap().queue.len(), 2);
+    }
+}
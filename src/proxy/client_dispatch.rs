@@ -1,15 +1,15 @@
-use crate::backend::LspBackend;
-use crate::backend_pool::{
-    shutdown_backend_instance, spawn_reader_task, warmup_timeout, BackendInstance, WarmupState,
-};
+use crate::backend::{BackendKind, LspBackend};
+use crate::backend_pool::shutdown_backend_instance;
 use crate::error::ProxyError;
 use crate::framing::LspFrameWriter;
 use crate::message::{RpcId, RpcMessage};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::time::Instant;
 
+use super::fanout::FANOUT_METHODS;
+
 /// LSP methods that depend on the cross-file index and should be queued during warmup.
-const INDEX_DEPENDENT_METHODS: &[&str] = &[
+pub(super) const INDEX_DEPENDENT_METHODS: &[&str] = &[
     "textDocument/definition",
     "textDocument/references",
     "textDocument/implementation",
@@ -24,13 +24,13 @@ impl super::LspProxy {
     pub(crate) async fn dispatch_initialize(
         &mut self,
         msg: &RpcMessage,
-        pending_initial_backend: &mut Option<(LspBackend, PathBuf)>,
+        pending_initial_backend: &mut Option<(LspBackend, PathBuf, BackendKind)>,
         client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
     ) -> Result<(), ProxyError> {
         tracing::info!("Caching initialize message for backend initialization");
         self.state.client_initialize = Some(msg.clone());
 
-        if let Some((mut backend, venv)) = pending_initial_backend.take() {
+        if let Some((mut backend, venv, kind)) = pending_initial_backend.take() {
             // Forward initialize to the pre-spawned backend
             match self
                 .complete_backend_initialization(&mut backend, &venv, client_writer)
@@ -39,28 +39,18 @@ impl super::LspProxy {
                 Ok(init_response) => {
                     // Split and insert into pool
                     let session = self.state.pool.next_session_id();
-                    let parts = backend.into_split();
-                    let tx = self.state.pool.msg_sender();
-                    let reader_task = spawn_reader_task(parts.reader, tx, venv.clone(), session);
-
-                    let timeout = warmup_timeout();
-                    let instance = BackendInstance {
-                        writer: parts.writer,
-                        child: parts.child,
-                        venv_path: venv.clone(),
+                    crate::backend::log_backend_executable(
+                        kind,
+                        backend.executable(),
+                        &venv,
                         session,
-                        last_used: Instant::now(),
-                        reader_task,
-                        next_id: parts.next_id,
-                        warmup_state: if timeout.is_zero() {
-                            WarmupState::Ready
-                        } else {
-                            WarmupState::Warming
-                        },
-                        warmup_deadline: Instant::now() + timeout,
-                        warmup_queue: Vec::new(),
-                    };
-                    self.state.pool.insert(venv, instance);
+                        &init_response,
+                    );
+                    let instance = self.build_backend_instance(backend, &venv, kind, session);
+                    if let Some(displaced) = self.state.pool.insert(instance) {
+                        self.shutdown_removed_backend(displaced, client_writer)
+                            .await?;
+                    }
 
                     // Send initialize response to client
                     client_writer.write_message(&init_response).await?;
@@ -103,8 +93,15 @@ impl super::LspProxy {
     /// Handle client "initialized" notification.
     ///
     /// Forwards the notification to all backends in the pool.
-    pub(crate) async fn dispatch_initialized(&mut self) -> Result<(), ProxyError> {
+    pub(crate) async fn dispatch_initialized(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
         tracing::info!("Client initialized");
+        if let Some(error) = self.config_error.take() {
+            self.notify_config_error(&error, client_writer).await;
+        }
+        self.start_prewarm_discovery();
         // Forward to all backends in the pool
         let initialized_msg = RpcMessage {
             jsonrpc: "2.0".to_string(),
@@ -118,7 +115,7 @@ impl super::LspProxy {
         let venvs: Vec<PathBuf> = self.state.pool.backends_keys();
         for venv in &venvs {
             if let Some(inst) = self.state.pool.get_mut(venv) {
-                if let Err(e) = inst.writer.write_message(&initialized_msg).await {
+                if let Err(e) = inst.send(&initialized_msg).await {
                     tracing::warn!(venv = %venv.display(), error = ?e, "Failed to forward initialized to backend");
                 }
             }
@@ -137,6 +134,10 @@ impl super::LspProxy {
     ) -> Result<(), ProxyError> {
         tracing::info!("Received shutdown request from client");
 
+        // Abort boots in flight (their processes are killed on drop)
+        self.boot.abort_all();
+        self.history.flush();
+
         // Shutdown all backends in the pool
         let venvs: Vec<PathBuf> = self.state.pool.backends_keys();
         for venv in &venvs {
@@ -177,7 +178,7 @@ impl super::LspProxy {
 
                 if let Some(inst) = self.state.pool.get_mut(&pending.venv_path) {
                     if inst.session == pending.session {
-                        if let Err(e) = inst.writer.write_message(&response_msg).await {
+                        if let Err(e) = inst.send(&response_msg).await {
                             tracing::warn!(
                                 venv = %pending.venv_path.display(),
                                 error = ?e,
@@ -328,49 +329,52 @@ impl super::LspProxy {
             }
         }
 
+        // URI-less requests: route by document URIs in other params or the workspace folder
+        // (fan-out methods are answered by all backends instead)
+        if target_venv.is_none()
+            && Self::extract_text_document_uri(msg).is_none()
+            && !method.is_some_and(|m| FANOUT_METHODS.contains(&m))
+        {
+            target_venv = self.route_uri_less_request(msg, client_writer).await?;
+        }
+
         // If we have a target, send to that backend
         if let Some(ref venv_path) = target_venv {
-            if let Some(inst) = self.state.pool.get_mut(venv_path) {
-                inst.last_used = Instant::now();
-                let session = inst.session;
-
-                // Queue index-dependent requests during warmup
-                if let Some(method_name) = method {
-                    if inst.is_warming() && INDEX_DEPENDENT_METHODS.contains(&method_name) {
-                        // Register in pending requests (so cancel/crash handling works)
-                        if let Some(id) = &msg.id {
-                            self.state.pending_requests.insert(
-                                id.clone(),
-                                crate::state::PendingRequest {
-                                    backend_session: session,
-                                    venv_path: venv_path.clone(),
-                                },
+            if let Some(session) = self.state.pool.get(venv_path).map(|inst| inst.session) {
+                // Registered in pending requests even when queued (so cancel/crash handling works)
+                let forwarded = self.register_pending_request(msg, venv_path, session);
+                if let Some(inst) = self.state.pool.get_mut(venv_path) {
+                    inst.last_used = Instant::now();
+
+                    // Queue index-dependent requests during warmup
+                    if let Some(method_name) = method {
+                        if inst.is_warming() && INDEX_DEPENDENT_METHODS.contains(&method_name) {
+                            tracing::info!(
+                                method = method_name,
+                                id = ?msg.id,
+                                venv = %venv_path.display(),
+                                "Queueing index-dependent request during warmup"
                             );
+                            inst.warmup_queue.push(forwarded);
+                            return Ok(());
                         }
-                        tracing::info!(
-                            method = method_name,
-                            id = ?msg.id,
-                            venv = %venv_path.display(),
-                            "Queueing index-dependent request during warmup"
-                        );
-                        inst.warmup_queue.push(msg.clone());
-                        return Ok(());
                     }
-                }
 
-                // Register in pending requests
-                if let Some(id) = &msg.id {
-                    self.state.pending_requests.insert(
-                        id.clone(),
-                        crate::state::PendingRequest {
-                            backend_session: session,
-                            venv_path: venv_path.clone(),
-                        },
-                    );
+                    if let Err(e) = inst.send(&forwarded).await {
+                        tracing::error!(venv = %venv_path.display(), error = ?e, "Failed to send request to backend");
+                    }
                 }
-
-                if let Err(e) = inst.writer.write_message(msg).await {
-                    tracing::error!(venv = %venv_path.display(), error = ?e, "Failed to send request to backend");
+            } else if let Some(session) = self.boot.get_mut(venv_path).map(|boot| boot.session) {
+                // Backend still booting: forwarded once it joins the pool
+                let forwarded = self.register_pending_request(msg, venv_path, session);
+                tracing::info!(
+                    method = ?method,
+                    id = ?msg.id,
+                    venv = %venv_path.display(),
+                    "Queueing request while backend boots"
+                );
+                if let Some(boot) = self.boot.get_mut(venv_path) {
+                    boot.queue.push(forwarded);
                 }
             } else {
                 // Backend disappeared (race with crash handling)
@@ -380,7 +384,15 @@ impl super::LspProxy {
             }
         } else {
             // No target venv resolved (URI-less request)
-            if self.state.pool.is_empty() {
+            if let (true, Some(venv_path)) = (self.state.pool.is_empty(), self.boot.single()) {
+                // The only backend is still booting: no cross-contamination possible
+                if let Some(session) = self.boot.get_mut(&venv_path).map(|boot| boot.session) {
+                    let forwarded = self.register_pending_request(msg, &venv_path, session);
+                    if let Some(boot) = self.boot.get_mut(&venv_path) {
+                        boot.queue.push(forwarded);
+                    }
+                }
+            } else if self.state.pool.is_empty() {
                 let error_message =
                     "lsp-proxy: .venv not found (strict mode). Create .venv or run hooks.";
                 let error_response = RpcMessage::error_response(msg, error_message);
@@ -388,6 +400,9 @@ impl super::LspProxy {
             } else if self.state.pool.len() == 1 {
                 // Single backend: no cross-contamination possible, forward unconditionally
                 self.forward_to_first_backend(msg).await?;
+            } else if method.is_some_and(|m| FANOUT_METHODS.contains(&m)) {
+                // Cross-project queries: ask every backend and merge the answers
+                self.start_fanout(msg, client_writer).await?;
             } else {
                 // Multiple backends: cannot determine target for URI-less requests
                 let method_name = msg.method_name().unwrap_or("");
@@ -410,26 +425,44 @@ impl super::LspProxy {
         Ok(())
     }
 
+    /// Register a client request in `pending_requests` under a new backend request ID
+    /// and start its timeout. Returns the request to send (or queue) for the backend,
+    /// carrying that ID; the response is mapped back to the client's ID.
+    fn register_pending_request(
+        &mut self,
+        msg: &RpcMessage,
+        venv_path: &Path,
+        session: u64,
+    ) -> RpcMessage {
+        let forwarded = self.state.register_request(msg, venv_path, session);
+        let Some(backend_id) = forwarded.id.as_ref() else {
+            return forwarded;
+        };
+        self.request_timeouts.track(
+            backend_id,
+            self.state
+                .config
+                .request_timeout(msg.method_name().unwrap_or_default()),
+        );
+        forwarded
+    }
+
     /// Forward a request to the first available backend in the pool.
     ///
     /// Used when no specific target venv is resolved but forwarding is safe
     /// (e.g., single-backend pool where no cross-contamination is possible).
     async fn forward_to_first_backend(&mut self, msg: &RpcMessage) -> Result<(), ProxyError> {
-        let first_venv = self.state.pool.first_key().cloned();
-        if let Some(venv_path) = first_venv {
+        let first = self
+            .state
+            .pool
+            .first_key()
+            .cloned()
+            .and_then(|venv| Some((self.state.pool.get(&venv)?.session, venv)));
+        if let Some((session, venv_path)) = first {
+            let forwarded = self.register_pending_request(msg, &venv_path, session);
             if let Some(inst) = self.state.pool.get_mut(&venv_path) {
                 inst.last_used = Instant::now();
-                let session = inst.session;
-                if let Some(id) = &msg.id {
-                    self.state.pending_requests.insert(
-                        id.clone(),
-                        crate::state::PendingRequest {
-                            backend_session: session,
-                            venv_path: venv_path.clone(),
-                        },
-                    );
-                }
-                if let Err(e) = inst.writer.write_message(msg).await {
+                if let Err(e) = inst.send(&forwarded).await {
                     tracing::error!(venv = %venv_path.display(), error = ?e, "Failed to send request to backend");
                 }
             }
@@ -439,15 +472,21 @@ impl super::LspProxy {
 
     /// Handle a generic client notification (not handled by specific handlers above).
     ///
-    /// Forwards to all backends in the pool.
+    /// Forwards to all backends in the pool, and queues for backends still booting.
     pub(crate) async fn dispatch_client_notification(
         &mut self,
         msg: &RpcMessage,
     ) -> Result<(), ProxyError> {
+        for venv in self.boot.venvs() {
+            if let Some(boot) = self.boot.get_mut(&venv) {
+                boot.queue.push(msg.clone());
+            }
+        }
+
         let venvs: Vec<PathBuf> = self.state.pool.backends_keys();
         for venv in &venvs {
             if let Some(inst) = self.state.pool.get_mut(venv) {
-                if let Err(e) = inst.writer.write_message(msg).await {
+                if let Err(e) = inst.send(msg).await {
                     tracing::warn!(venv = %venv.display(), error = ?e, "Failed to forward notification to backend");
                 }
             }
@@ -458,39 +497,98 @@ impl super::LspProxy {
 
     /// Handle `$/cancelRequest` notification.
     ///
-    /// If the target request is queued in a warmup queue, remove it without
-    /// forwarding to the backend. Otherwise, forward the cancel to all backends.
+    /// Requests still queued (boot or warmup) are removed and answered with
+    /// `RequestCancelled`; fan-out requests are cancelled in every backend.
+    /// Otherwise the cancel is sent (with the backend request ID) to the owning backend
+    /// only, and the request is answered by the proxy if the backend doesn't reply
+    /// within `CANCEL_GRACE`. Cancels for requests whose backend is gone are dropped.
     pub(crate) async fn dispatch_cancel_request(
         &mut self,
         msg: &RpcMessage,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
     ) -> Result<(), ProxyError> {
-        if let Some(cancelled_id) = extract_cancel_id(msg) {
-            if let Some(pending) = self.state.pending_requests.get(&cancelled_id).cloned() {
-                if let Some(inst) = self.state.pool.get_mut(&pending.venv_path) {
-                    if inst.session == pending.backend_session
-                        && inst.cancel_warmup_request(&cancelled_id).is_some()
-                    {
-                        tracing::info!(
-                            id = ?cancelled_id,
-                            venv = %pending.venv_path.display(),
-                            "Cancelled warmup-queued request"
-                        );
-                        self.state.pending_requests.remove(&cancelled_id);
-                        return Ok(());
-                    }
-                }
-            }
+        let Some(client_id) = extract_cancel_id(msg) else {
+            return Ok(());
+        };
+        if self.fanout.contains(&client_id) {
+            return self.cancel_fanout(&client_id, client_writer).await;
+        }
+        let Some(target) = super::timeouts::cancel_target(&self.state, &client_id) else {
+            tracing::debug!(id = ?client_id, "Ignoring cancel for a request that is not pending");
+            return Ok(());
+        };
+        let cancelled_id = target.backend_id;
+
+        let queued = if self
+            .boot
+            .cancel_queued_request(&target.venv_path, &cancelled_id)
+            .is_some()
+        {
+            Some("Cancelled request queued during boot")
+        } else if target.owner_alive
+            && self
+                .state
+                .pool
+                .get_mut(&target.venv_path)
+                .is_some_and(|inst| inst.cancel_warmup_request(&cancelled_id).is_some())
+        {
+            Some("Cancelled warmup-queued request")
+        } else {
+            None
+        };
+        if let Some(message) = queued {
+            tracing::info!(id = ?client_id, venv = %target.venv_path.display(), "{}", message);
+            self.state.remove_pending(&cancelled_id);
+            let response =
+                RpcMessage::cancelled_response(client_id, "lsp-proxy: request cancelled");
+            client_writer.write_message(&response).await?;
+            return Ok(());
         }
 
-        // Not in warmup queue — forward $/cancelRequest to all backends
-        self.dispatch_client_notification(msg).await
+        let Some(inst) = self
+            .state
+            .pool
+            .get_mut(&target.venv_path)
+            .filter(|inst| target.owner_alive && inst.session == target.session)
+        else {
+            tracing::debug!(
+                id = ?client_id,
+                venv = %target.venv_path.display(),
+                "Dropping cancel: owning backend is gone"
+            );
+            return Ok(());
+        };
+        if let Err(e) = inst
+            .send(&RpcMessage::cancel_notification(&cancelled_id))
+            .await
+        {
+            tracing::warn!(venv = %target.venv_path.display(), error = ?e, "Failed to forward cancel to backend");
+        }
+        tracing::debug!(
+            id = ?client_id,
+            backend_id = ?cancelled_id,
+            venv = %target.venv_path.display(),
+            "Forwarded cancel to owning backend"
+        );
+        if let Some(entry) = self
+            .state
+            .pending_requests
+            .get_mut(&cancelled_id)
+            .filter(|entry| entry.cancelled_at.is_none())
+        {
+            let now = Instant::now();
+            entry.cancelled_at = Some(now);
+            self.request_timeouts
+                .schedule(cancelled_id, now + super::timeouts::CANCEL_GRACE);
+        }
+        Ok(())
     }
 
     /// Forward queued warmup requests to the backend now that it is ready.
     /// `expected_session` is checked to avoid forwarding to a replaced backend.
     pub(crate) async fn drain_warmup_queue(
         &mut self,
-        venv_path: &PathBuf,
+        venv_path: &Path,
         expected_session: u64,
         queued: Vec<RpcMessage>,
         client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
@@ -515,14 +613,21 @@ impl super::LspProxy {
                 );
                 // Remove remaining queued requests from pending_requests
                 if let Some(req_id) = &request.id {
-                    self.state.pending_requests.remove(req_id);
+                    self.state.remove_pending(req_id);
                 }
                 continue;
             }
 
             if let Some(inst) = self.state.pool.get_mut(venv_path) {
-                match inst.writer.write_message(&request).await {
+                match inst.send(&request).await {
                     Ok(()) => {
+                        if let Some(pending) = request
+                            .id
+                            .as_ref()
+                            .and_then(|id| self.state.pending_requests.get_mut(id))
+                        {
+                            pending.sent_at = Instant::now();
+                        }
                         tracing::info!(
                             method = %method,
                             id = %id_debug,
@@ -539,14 +644,17 @@ impl super::LspProxy {
                             "Failed to forward warmup-queued request"
                         );
                         // Remove from pending_requests and send error to client
-                        if let Some(req_id) = &request.id {
-                            self.state.pending_requests.remove(req_id);
+                        if let Some(pending) = request
+                            .id
+                            .as_ref()
+                            .and_then(|id| self.state.remove_pending(id))
+                        {
+                            let error_response = RpcMessage::internal_error_response(
+                                pending.client_id,
+                                "lsp-proxy: backend write failed during warmup drain",
+                            );
+                            client_writer.write_message(&error_response).await?;
                         }
-                        let error_response = RpcMessage::error_response(
-                            &request,
-                            "lsp-proxy: backend write failed during warmup drain",
-                        );
-                        client_writer.write_message(&error_response).await?;
                     }
                 }
             }
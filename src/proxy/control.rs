@@ -0,0 +1,209 @@
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::RpcMessage;
+use crate::venv;
+use std::path::PathBuf;
+use tokio::time::Instant;
+
+/// Pin or unpin a venv: `{"venv": "<path>"}` or `{"uri": "<document uri>"}`,
+/// plus `"pinned": false` to unpin
+pub(crate) const PIN_METHOD: &str = "typemux/pin";
+
+/// Report pool state (backends, booting venvs, pins)
+pub(crate) const STATUS_METHOD: &str = "typemux/status";
+
+impl super::LspProxy {
+    /// Handle `typemux/pin`: answered by the proxy, never forwarded to a backend
+    pub(crate) async fn dispatch_pin(
+        &mut self,
+        msg: &RpcMessage,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let params = msg.params.as_ref();
+        let pinned = params
+            .and_then(|p| p.get("pinned"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+
+        let venv_path = match self.resolve_pin_target(msg).await {
+            Ok(venv_path) => venv_path,
+            Err(message) => {
+                let error_response = RpcMessage::error_response(msg, &message);
+                client_writer.write_message(&error_response).await?;
+                return Ok(());
+            }
+        };
+
+        if self.state.pool.set_pinned(venv_path.clone(), pinned) {
+            tracing::info!(venv = %venv_path.display(), pinned = pinned, "Venv pin changed");
+        }
+
+        let response = RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: msg.id.clone(),
+            method: None,
+            params: None,
+            result: Some(serde_json::json!({
+                "venv": venv_path,
+                "pinned": pinned,
+            })),
+            error: None,
+        };
+        client_writer.write_message(&response).await?;
+        Ok(())
+    }
+
+    /// Venv named by a `typemux/pin` request, or the error message to answer it with
+    async fn resolve_pin_target(&self, msg: &RpcMessage) -> Result<PathBuf, String> {
+        let no_target = || {
+            "lsp-proxy: typemux/pin needs a \"venv\" path or a \"uri\" inside a venv".to_string()
+        };
+        let Some(params) = &msg.params else {
+            return Err(no_target());
+        };
+
+        if let Some(path) = params.get("venv").and_then(|v| v.as_str()) {
+            let path = PathBuf::from(path);
+            // Relative paths are resolved like `pinned_venvs` in .typemux.toml
+            return Ok(match &self.state.git_toplevel {
+                Some(toplevel) if path.is_relative() => toplevel.join(path),
+                _ => path,
+            });
+        }
+
+        let Some(url) = params
+            .get("uri")
+            .and_then(|v| v.as_str())
+            .and_then(|uri| url::Url::parse(uri).ok())
+        else {
+            return Err(no_target());
+        };
+        if let Some(venv_path) = self.venv_for_uri(&url) {
+            return Ok(venv_path);
+        }
+        let Ok(file_path) = url.to_file_path() else {
+            return Err(no_target());
+        };
+        // e.g. a venv rule pointing at a missing env: answer the request, keep running
+        match venv::find_venv(
+            &file_path,
+            self.state.git_toplevel.as_deref(),
+            &self.state.config,
+        )
+        .await
+        {
+            Ok(Some(venv_path)) => Ok(venv_path),
+            Ok(None) => Err(no_target()),
+            Err(e) => {
+                tracing::warn!(uri = %url, error = ?e, "venv resolution failed for typemux/pin");
+                Err(format!("lsp-proxy: typemux/pin: {e}"))
+            }
+        }
+    }
+
+    /// Handle `typemux/status`: a snapshot of the pool for debugging
+    pub(crate) async fn dispatch_status(
+        &mut self,
+        msg: &RpcMessage,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let now = Instant::now();
+        let mut venvs = self.state.pool.backends_keys();
+        venvs.sort();
+
+        let backends: Vec<serde_json::Value> = venvs
+            .iter()
+            .filter_map(|venv_path| self.state.pool.get(venv_path))
+            .map(|inst| {
+                let pending = self
+                    .state
+                    .pending_requests
+                    .values()
+                    .filter(|p| p.venv_path == inst.venv_path && p.backend_session == inst.session)
+                    .count();
+                serde_json::json!({
+                    "venv": inst.venv_path,
+                    "backend": inst.kind.display_name(),
+                    "session": inst.session,
+                    "state": if inst.is_suspended() {
+                        "suspended"
+                    } else if inst.is_warming() {
+                        "warming"
+                    } else {
+                        "ready"
+                    },
+                    "pinned": self.state.pool.is_pinned(&inst.venv_path),
+                    "pendingRequests": pending,
+                    "idleSecs": now.duration_since(inst.last_used).as_secs(),
+                    "rssBytes": inst.rss_bytes,
+                })
+            })
+            .collect();
+
+        let mut booting = self.boot.venvs();
+        booting.sort();
+
+        let response = RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: msg.id.clone(),
+            method: None,
+            params: None,
+            result: Some(serde_json::json!({
+                "maxBackends": self.state.pool.max_backends(),
+                "backends": backends,
+                "booting": booting,
+                "pinned": self.state.pool.pinned_venvs(),
+            })),
+            error: None,
+        };
+        client_writer.write_message(&response).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::BackendKind;
+    use crate::config::{Config, ConfigOverrides};
+
+    fn pin_request(params: serde_json::Value) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(crate::message::RpcId::Number(1)),
+            method: Some(PIN_METHOD.to_string()),
+            params: Some(params),
+            result: None,
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn pin_target_with_missing_rule_target_is_an_error_answer() {
+        let temp = tempfile::tempdir().unwrap();
+        let service = temp.path().join("services/api");
+        std::fs::create_dir_all(&service).unwrap();
+        let file = service.join("main.py");
+        std::fs::write(&file, "# test").unwrap();
+
+        let mut proxy =
+            crate::proxy::LspProxy::new(BackendKind::Pyright, 4, None, ConfigOverrides::default());
+        proxy.state.git_toplevel = Some(temp.path().to_path_buf());
+        proxy.state.config = toml::from_str::<Config>(
+            "[[venv_rules]]\npattern = \"services/*\"\nvenv = \"tools/envs/services-venv\"\n",
+        )
+        .unwrap();
+
+        let uri = url::Url::from_file_path(&file).unwrap();
+        let result = proxy
+            .resolve_pin_target(&pin_request(serde_json::json!({ "uri": uri.as_str() })))
+            .await;
+        let message = result.unwrap_err();
+        assert!(message.contains("services-venv"), "{message}");
+
+        let result = proxy
+            .resolve_pin_target(&pin_request(serde_json::json!({ "venv": "a/.venv" })))
+            .await;
+        assert_eq!(result, Ok(temp.path().join("a/.venv")));
+    }
+}
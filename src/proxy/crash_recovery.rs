@@ -0,0 +1,280 @@
+use crate::error::{BackendError, ProxyError};
+use crate::framing::LspFrameWriter;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Delay before respawning after the first crash in the window; doubled for each further crash
+const RESPAWN_BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Upper bound for the respawn delay
+const RESPAWN_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// What to do after a backend crash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CrashVerdict {
+    /// Respawn after this delay
+    Respawn(Duration),
+    /// Too many crashes within the window: don't respawn
+    Quarantine { crashes: usize },
+}
+
+/// Recent crashes and scheduled respawns per venv
+#[derive(Default)]
+pub(crate) struct CrashRecoveryState {
+    /// Crash times within the current window, oldest first
+    crashes: HashMap<PathBuf, VecDeque<Instant>>,
+    /// Respawn each venv's backend once its deadline passes
+    respawn_deadlines: HashMap<PathBuf, Instant>,
+}
+
+impl CrashRecoveryState {
+    /// Record a crash and decide whether (and when) to respawn.
+    /// The `limit`-th crash within `window` quarantines the venv.
+    pub fn record_crash(
+        &mut self,
+        venv_path: &Path,
+        now: Instant,
+        limit: u32,
+        window: Duration,
+    ) -> CrashVerdict {
+        let crashes = self.crashes.entry(venv_path.to_path_buf()).or_default();
+        crashes.push_back(now);
+        prune(crashes, now, window);
+
+        if crashes.len() >= limit as usize {
+            self.respawn_deadlines.remove(venv_path);
+            return CrashVerdict::Quarantine {
+                crashes: crashes.len(),
+            };
+        }
+        CrashVerdict::Respawn(backoff(crashes.len()))
+    }
+
+    /// Whether `venv_path` crashed `limit` times within `window` (no respawns, no new backends)
+    pub fn is_quarantined(
+        &mut self,
+        venv_path: &Path,
+        now: Instant,
+        limit: u32,
+        window: Duration,
+    ) -> bool {
+        self.recent_crashes(venv_path, now, window) >= limit as usize
+    }
+
+    /// Whether `venv_path` crashed within `window` (its boots are crash recovery)
+    pub fn is_recovering(&mut self, venv_path: &Path, now: Instant, window: Duration) -> bool {
+        self.recent_crashes(venv_path, now, window) > 0
+    }
+
+    fn recent_crashes(&mut self, venv_path: &Path, now: Instant, window: Duration) -> usize {
+        let Some(crashes) = self.crashes.get_mut(venv_path) else {
+            return 0;
+        };
+        prune(crashes, now, window);
+        if crashes.is_empty() {
+            self.crashes.remove(venv_path);
+            return 0;
+        }
+        crashes.len()
+    }
+
+    pub fn schedule_respawn(&mut self, venv_path: &Path, deadline: Instant) {
+        self.respawn_deadlines
+            .insert(venv_path.to_path_buf(), deadline);
+    }
+
+    /// Nearest scheduled respawn
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.respawn_deadlines.values().copied().min()
+    }
+
+    /// Remove and return venvs whose respawn is due
+    pub fn take_due(&mut self, now: Instant) -> Vec<PathBuf> {
+        let due: Vec<PathBuf> = self
+            .respawn_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(venv_path, _)| venv_path.clone())
+            .collect();
+        for venv_path in &due {
+            self.respawn_deadlines.remove(venv_path);
+        }
+        due
+    }
+}
+
+/// Drop crashes older than `window`
+fn prune(crashes: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while crashes
+        .front()
+        .is_some_and(|t| now.duration_since(*t) >= window)
+    {
+        crashes.pop_front();
+    }
+}
+
+/// Respawn delay after the `n`-th crash in the window (1s, 2s, 4s, ... up to 60s)
+fn backoff(n: usize) -> Duration {
+    let exp = n.saturating_sub(1).min(16) as u32;
+    RESPAWN_BACKOFF_BASE
+        .saturating_mul(2u32.pow(exp))
+        .min(RESPAWN_BACKOFF_MAX)
+}
+
+impl super::LspProxy {
+    /// Error if `venv_path` is quarantined after repeated crashes
+    pub(crate) fn check_quarantine(&mut self, venv_path: &Path) -> Result<(), ProxyError> {
+        let config = &self.state.config;
+        let (limit, window) = (config.crash_limit, config.crash_window());
+        if self
+            .crash_recovery
+            .is_quarantined(venv_path, Instant::now(), limit, window)
+        {
+            return Err(ProxyError::Backend(BackendError::Quarantined {
+                crashes: limit as usize,
+                window_secs: window.as_secs(),
+            }));
+        }
+        Ok(())
+    }
+
+    /// Whether a boot of `venv_path` is an attempt to recover from a recent crash
+    pub(crate) fn is_recovering_from_crash(&mut self, venv_path: &Path) -> bool {
+        let window = self.state.config.crash_window();
+        self.crash_recovery
+            .is_recovering(venv_path, Instant::now(), window)
+    }
+
+    /// Record a crash (or failed respawn) of a venv's backend and schedule a respawn
+    /// with exponential backoff, or quarantine the venv and tell the user.
+    pub(crate) async fn schedule_crash_respawn(
+        &mut self,
+        venv_path: &Path,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) {
+        let config = &self.state.config;
+        let (limit, window) = (config.crash_limit, config.crash_window());
+        let now = Instant::now();
+
+        match self
+            .crash_recovery
+            .record_crash(venv_path, now, limit, window)
+        {
+            CrashVerdict::Respawn(delay) => {
+                tracing::info!(
+                    venv = %venv_path.display(),
+                    delay_ms = delay.as_millis() as u64,
+                    "Scheduling backend respawn after crash"
+                );
+                self.crash_recovery.schedule_respawn(venv_path, now + delay);
+            }
+            CrashVerdict::Quarantine { crashes } => {
+                tracing::error!(
+                    venv = %venv_path.display(),
+                    crashes = crashes,
+                    window_secs = window.as_secs(),
+                    "Backend keeps crashing, quarantining venv"
+                );
+                let e = ProxyError::Backend(BackendError::Quarantined {
+                    crashes,
+                    window_secs: window.as_secs(),
+                });
+                self.notify_backend_error(venv_path, &e, client_writer)
+                    .await;
+                // No backend will refresh them
+                self.clear_diagnostics_for_venv(venv_path, client_writer)
+                    .await;
+            }
+        }
+    }
+
+    /// Respawn crashed backends whose backoff elapsed, if their venv still has open documents
+    pub(crate) async fn respawn_crashed_backends(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        for venv_path in self.crash_recovery.take_due(Instant::now()) {
+            let has_documents = self
+                .state
+                .open_documents
+                .values()
+                .any(|doc| doc.venv.as_ref() == Some(&venv_path));
+            if !has_documents
+                || self.state.pool.contains(&venv_path)
+                || self.boot.contains(&venv_path)
+            {
+                continue;
+            }
+
+            tracing::info!(venv = %venv_path.display(), "Respawning crashed backend");
+            // Documents are restored by the boot; a fresh warmup starts with it
+            let kind = self.backend_kind_for(&venv_path, None);
+            if let Err(e) = self
+                .start_backend_boot(&venv_path, kind, false, client_writer)
+                .await
+            {
+                tracing::error!(venv = %venv_path.display(), error = ?e, "Failed to respawn crashed backend");
+                self.notify_backend_error(&venv_path, &e, client_writer)
+                    .await;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+        assert_eq!(backoff(30), RESPAWN_BACKOFF_MAX);
+    }
+
+    #[test]
+    fn quarantine_after_limit_within_window() {
+        let mut state = CrashRecoveryState::default();
+        let venv = Path::new("/p/.venv");
+        let window = Duration::from_secs(60);
+        let t0 = Instant::now();
+
+        assert_eq!(
+            state.record_crash(venv, t0, 3, window),
+            CrashVerdict::Respawn(Duration::from_secs(1))
+        );
+        assert_eq!(
+            state.record_crash(venv, t0 + Duration::from_secs(5), 3, window),
+            CrashVerdict::Respawn(Duration::from_secs(2))
+        );
+        assert_eq!(
+            state.record_crash(venv, t0 + Duration::from_secs(10), 3, window),
+            CrashVerdict::Quarantine { crashes: 3 }
+        );
+        assert!(state.is_quarantined(venv, t0 + Duration::from_secs(30), 3, window));
+
+        // The first crash leaves the window: new backends are allowed again
+        assert!(!state.is_quarantined(venv, t0 + Duration::from_secs(61), 3, window));
+        assert!(state.is_recovering(venv, t0 + Duration::from_secs(61), window));
+        assert!(!state.is_recovering(venv, t0 + Duration::from_secs(71), window));
+    }
+
+    #[test]
+    fn take_due_returns_elapsed_respawns() {
+        let mut state = CrashRecoveryState::default();
+        let t0 = Instant::now();
+        state.schedule_respawn(Path::new("/a/.venv"), t0 + Duration::from_secs(1));
+        state.schedule_respawn(Path::new("/b/.venv"), t0 + Duration::from_secs(5));
+
+        assert_eq!(state.next_deadline(), Some(t0 + Duration::from_secs(1)));
+        assert_eq!(
+            state.take_due(t0 + Duration::from_secs(2)),
+            vec![PathBuf::from("/a/.venv")]
+        );
+        assert_eq!(state.next_deadline(), Some(t0 + Duration::from_secs(5)));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::error::ProxyError;
+use crate::error::{ConfigError, ProxyError};
 use crate::framing::LspFrameWriter;
 use crate::message::RpcMessage;
 use std::path::Path;
@@ -35,6 +35,35 @@ impl super::LspProxy {
         }
     }
 
+    /// Send window/showMessage error to client when `.typemux.toml` could not be used
+    pub(crate) async fn notify_config_error(
+        &self,
+        error: &ConfigError,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) {
+        let msg = RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: Some("window/showMessage".to_string()),
+            params: Some(serde_json::json!({
+                "type": 1,
+                "message": format!(
+                    "typemux-cc: {} (using default settings)",
+                    error
+                )
+            })),
+            result: None,
+            error: None,
+        };
+
+        if let Err(e) = client_writer.write_message(&msg).await {
+            tracing::warn!(
+                error = ?e,
+                "Failed to send config error notification to client"
+            );
+        }
+    }
+
     /// Clear diagnostics for all documents belonging to a venv
     pub(crate) async fn clear_diagnostics_for_venv(
         &self,
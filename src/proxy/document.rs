@@ -70,7 +70,28 @@ impl super::LspProxy {
         );
 
         // Search for .venv
-        let found_venv = venv::find_venv(&file_path, self.state.git_toplevel.as_deref()).await?;
+        let found_venv = match venv::find_venv(
+            &file_path,
+            self.state.git_toplevel.as_deref(),
+            &self.state.config,
+        )
+        .await
+        {
+            Ok(found) => found,
+            Err(e) => {
+                // e.g. a config rule pointing at a missing env: surface it to the user,
+                // but still cache the document so its text stays up to date
+                tracing::error!(
+                    uri = uri_str,
+                    error = ?e,
+                    "venv resolution failed for didOpen"
+                );
+                let e = ProxyError::Venv(e);
+                self.notify_backend_error(&file_path, &e, client_writer)
+                    .await;
+                None
+            }
+        };
 
         // Cache document
         if let Some(text_content) = &text {
@@ -91,37 +112,32 @@ impl super::LspProxy {
             );
             return Ok(());
         };
+        self.history.touch(venv_path);
+        self.history.flush_if_due();
 
         if !self.state.pool.contains(venv_path) {
-            // Need to create backend
-            if self.state.pool.is_full() {
-                self.evict_lru_backend(client_writer).await?;
-            }
-
-            match self.create_backend_instance(venv_path, client_writer).await {
-                Ok(instance) => {
-                    self.state.pool.insert(venv_path.clone(), instance);
-                    // didOpen was already restored during create_backend_instance
-                    // (restore_documents_to_backend sends didOpen for matching docs)
-                    return Ok(());
-                }
-                Err(e) => {
-                    tracing::error!(
-                        venv = %venv_path.display(),
-                        error = ?e,
-                        "Failed to create backend for didOpen"
-                    );
-                    self.notify_backend_error(venv_path, &e, client_writer)
-                        .await;
-                    return Ok(());
-                }
+            let kind = self.backend_kind_for(venv_path, file_path.parent());
+            // Boot a backend in the background; once initialized it restores every
+            // cached document of this venv, including this one
+            if let Err(e) = self
+                .start_backend_boot(venv_path, kind, false, client_writer)
+                .await
+            {
+                tracing::error!(
+                    venv = %venv_path.display(),
+                    error = ?e,
+                    "Failed to create backend for didOpen"
+                );
+                self.notify_backend_error(venv_path, &e, client_writer)
+                    .await;
             }
+            return Ok(());
         }
 
         // Backend exists in pool — forward didOpen
         if let Some(inst) = self.state.pool.get_mut(venv_path) {
             inst.last_used = Instant::now();
-            if let Err(e) = inst.writer.write_message(msg).await {
+            if let Err(e) = inst.send(msg).await {
                 tracing::warn!(
                     venv = %venv_path.display(),
                     error = ?e,
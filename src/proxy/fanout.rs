@@ -0,0 +1,343 @@
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::{RpcError, RpcId, RpcMessage};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use tokio::time::Instant;
+
+/// URI-less requests answered by every pooled backend, with the results merged
+pub(crate) const FANOUT_METHODS: &[&str] = &["workspace/symbol"];
+
+/// A client request fanned out to several backends
+struct FanoutRequest {
+    request: RpcMessage,
+    /// Sub-request id → (venv, session) of the backends that haven't answered yet
+    outstanding: HashMap<RpcId, (PathBuf, u64)>,
+    results: Vec<Value>,
+    errors: Vec<RpcError>,
+    /// Backends the request was sent to
+    backends: usize,
+    deadline: Option<Instant>,
+}
+
+/// Fanned-out client requests, keyed by the client's request id
+#[derive(Default)]
+pub(crate) struct FanoutState {
+    requests: HashMap<RpcId, FanoutRequest>,
+    /// Sub-request id → client request id
+    sub_requests: HashMap<RpcId, RpcId>,
+}
+
+impl FanoutState {
+    /// Whether `id` belongs to a fan-out sub-request (its response must not reach the client as is)
+    pub fn is_sub_request(&self, id: &RpcId) -> bool {
+        self.sub_requests.contains_key(id)
+    }
+
+    pub fn contains(&self, client_id: &RpcId) -> bool {
+        self.requests.contains_key(client_id)
+    }
+
+    /// Nearest fan-out deadline
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.requests.values().filter_map(|req| req.deadline).min()
+    }
+}
+
+/// Merge `SymbolInformation[]` / `WorkspaceSymbol[]` results from several backends,
+/// dropping symbols reported by more than one (same name, kind, container and location)
+pub(crate) fn merge_symbol_results(results: Vec<Value>) -> Value {
+    let mut seen = HashSet::new();
+    let mut merged = Vec::new();
+    for symbol in results
+        .into_iter()
+        .filter_map(|result| match result {
+            Value::Array(symbols) => Some(symbols),
+            _ => None,
+        })
+        .flatten()
+    {
+        let key = serde_json::json!([
+            symbol.get("name"),
+            symbol.get("kind"),
+            symbol.get("containerName"),
+            symbol.get("location"),
+        ])
+        .to_string();
+        if seen.insert(key) {
+            merged.push(symbol);
+        }
+    }
+    Value::Array(merged)
+}
+
+impl super::LspProxy {
+    /// Send a URI-less request to every pooled backend under per-backend ids.
+    /// The merged response is sent once all backends answered or `fanout_timeout_secs` passed.
+    pub(crate) async fn start_fanout(
+        &mut self,
+        msg: &RpcMessage,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(client_id) = msg.id.clone() else {
+            return Ok(());
+        };
+        let method = msg.method_name().unwrap_or_default().to_string();
+
+        let mut outstanding = HashMap::new();
+        for venv_path in self.state.pool.backends_keys() {
+            let sub_id = self.state.alloc_backend_request_id();
+            let Some(inst) = self.state.pool.get_mut(&venv_path) else {
+                continue;
+            };
+            inst.last_used = Instant::now();
+            let mut sub_request = msg.clone();
+            sub_request.id = Some(sub_id.clone());
+            match inst.send(&sub_request).await {
+                Ok(()) => {
+                    outstanding.insert(sub_id.clone(), (venv_path, inst.session));
+                    self.fanout.sub_requests.insert(sub_id, client_id.clone());
+                }
+                Err(e) => {
+                    // A dead pipe surfaces as a crash through the reader task
+                    tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to send fan-out request");
+                }
+            }
+        }
+
+        tracing::info!(
+            method = %method,
+            id = ?client_id,
+            backends = outstanding.len(),
+            "Fanning out request to all backends"
+        );
+        let backends = outstanding.len();
+        self.fanout.requests.insert(
+            client_id.clone(),
+            FanoutRequest {
+                request: msg.clone(),
+                outstanding,
+                results: Vec::new(),
+                errors: Vec::new(),
+                backends,
+                deadline: self
+                    .state
+                    .config
+                    .fanout_timeout()
+                    .map(|timeout| Instant::now() + timeout),
+            },
+        );
+        if backends == 0 {
+            self.finish_fanout(&client_id, client_writer).await?;
+        }
+        Ok(())
+    }
+
+    /// Record a backend's response to a fan-out sub-request
+    pub(crate) async fn handle_fanout_response(
+        &mut self,
+        msg: RpcMessage,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(sub_id) = &msg.id else {
+            return Ok(());
+        };
+        let Some(client_id) = self.fanout.sub_requests.remove(sub_id) else {
+            tracing::debug!(id = ?sub_id, "Dropping late fan-out response");
+            return Ok(());
+        };
+        let Some(request) = self.fanout.requests.get_mut(&client_id) else {
+            return Ok(());
+        };
+        request.outstanding.remove(sub_id);
+        match (msg.result, msg.error) {
+            (_, Some(error)) => request.errors.push(error),
+            (Some(result), None) => request.results.push(result),
+            (None, None) => {}
+        }
+        if request.outstanding.is_empty() {
+            self.finish_fanout(&client_id, client_writer).await?;
+        }
+        Ok(())
+    }
+
+    /// Stop waiting for a backend that left the pool (crash, eviction, restart)
+    pub(crate) async fn fanout_backend_gone(
+        &mut self,
+        venv_path: &PathBuf,
+        session: u64,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let mut finished = Vec::new();
+        for (client_id, request) in &mut self.fanout.requests {
+            let before = request.outstanding.len();
+            request
+                .outstanding
+                .retain(|_, (venv, sub_session)| !(venv == venv_path && *sub_session == session));
+            if request.outstanding.len() < before && request.outstanding.is_empty() {
+                finished.push(client_id.clone());
+            }
+        }
+        for client_id in finished {
+            self.finish_fanout(&client_id, client_writer).await?;
+        }
+        Ok(())
+    }
+
+    /// Answer fan-out requests past their deadline with the results gathered so far
+    pub(crate) async fn expire_fanouts(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let now = Instant::now();
+        let expired: Vec<RpcId> = self
+            .fanout
+            .requests
+            .iter()
+            .filter(|(_, request)| request.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for client_id in expired {
+            self.finish_fanout(&client_id, client_writer).await?;
+        }
+        Ok(())
+    }
+
+    /// Cancel a fan-out request on the client's `$/cancelRequest`
+    pub(crate) async fn cancel_fanout(
+        &mut self,
+        client_id: &RpcId,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(request) = self.fanout.requests.remove(client_id) else {
+            return Ok(());
+        };
+        tracing::info!(id = ?client_id, "Cancelled fan-out request");
+        self.cancel_fanout_sub_requests(request.outstanding).await;
+        let response =
+            RpcMessage::cancelled_response(client_id.clone(), "lsp-proxy: request cancelled");
+        client_writer.write_message(&response).await?;
+        Ok(())
+    }
+
+    /// Send the merged response (an error only if every backend failed)
+    async fn finish_fanout(
+        &mut self,
+        client_id: &RpcId,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(request) = self.fanout.requests.remove(client_id) else {
+            return Ok(());
+        };
+        let method = request
+            .request
+            .method_name()
+            .unwrap_or_default()
+            .to_string();
+        let answered = request.results.len() + request.errors.len();
+        if !request.outstanding.is_empty() {
+            tracing::warn!(
+                method = %method,
+                id = ?client_id,
+                answered = answered,
+                backends = request.backends,
+                "Fan-out deadline passed, returning partial results"
+            );
+        }
+        let timed_out = request.outstanding.len();
+        self.cancel_fanout_sub_requests(request.outstanding).await;
+
+        let response = match request.errors.into_iter().next() {
+            Some(error) if request.results.is_empty() => RpcMessage {
+                jsonrpc: "2.0".to_string(),
+                id: Some(client_id.clone()),
+                method: None,
+                params: None,
+                result: None,
+                error: Some(error),
+            },
+            _ if request.backends == 0 => {
+                RpcMessage::error_response(&request.request, "lsp-proxy: no backend available")
+            }
+            _ => {
+                let merged = merge_symbol_results(request.results);
+                tracing::info!(
+                    method = %method,
+                    id = ?client_id,
+                    backends = request.backends,
+                    timed_out = timed_out,
+                    symbols = merged.as_array().map_or(0, Vec::len),
+                    "Merged fan-out results"
+                );
+                RpcMessage {
+                    jsonrpc: "2.0".to_string(),
+                    id: Some(client_id.clone()),
+                    method: None,
+                    params: None,
+                    result: Some(merged),
+                    error: None,
+                }
+            }
+        };
+        client_writer.write_message(&response).await?;
+        Ok(())
+    }
+
+    /// Cancel unanswered sub-requests in their backends (late answers are dropped)
+    async fn cancel_fanout_sub_requests(&mut self, outstanding: HashMap<RpcId, (PathBuf, u64)>) {
+        for (sub_id, (venv_path, session)) in outstanding {
+            self.fanout.sub_requests.remove(&sub_id);
+            let Some(inst) = self
+                .state
+                .pool
+                .get_mut(&venv_path)
+                .filter(|inst| inst.session == session)
+            else {
+                continue;
+            };
+            if let Err(e) = inst.send(&RpcMessage::cancel_notification(&sub_id)).await {
+                tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to cancel fan-out request in backend");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_symbol_results_dedupes_across_backends() {
+        let shared = json!({
+            "name": "Config",
+            "kind": 5,
+            "location": {"uri": "file:///r/libs/config.py", "range": {"start": {"line": 1, "character": 0}, "end": {"line": 1, "character": 6}}},
+        });
+        let a =
+            json!([shared, {"name": "App", "kind": 5, "location": {"uri": "file:///r/a/app.py"}}]);
+        let b = json!([{"name": "Config", "kind": 5, "location": {"uri": "file:///r/b/config.py"}}, shared]);
+
+        let merged = merge_symbol_results(vec![a, Value::Null, b]);
+        let names: Vec<(&str, &str)> = merged
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                (
+                    s["name"].as_str().unwrap(),
+                    s["location"]["uri"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Config", "file:///r/libs/config.py"),
+                ("App", "file:///r/a/app.py"),
+                ("Config", "file:///r/b/config.py"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use super::timeouts::CANCEL_GRACE;
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::{RpcId, RpcMessage};
+use crate::state::ProxyState;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How often backends are checked for hangs (and probes sent, when due)
+pub(crate) const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Liveness probe method. Servers answer unknown `$/` requests with MethodNotFound,
+/// which is enough to prove the backend's message loop is running.
+const PROBE_METHOD: &str = "$/typemux/ping";
+
+/// A liveness probe sent to a backend and not answered yet
+struct Probe {
+    session: u64,
+    id: RpcId,
+    sent_at: Instant,
+}
+
+/// Outstanding liveness probes per venv
+#[derive(Default)]
+pub(crate) struct HealthState {
+    probes: HashMap<PathBuf, Probe>,
+    last_probe_round: Option<Instant>,
+}
+
+impl HealthState {
+    /// Record a probe response from `venv_path`.
+    /// Returns false if `id` is not the outstanding probe of that venv.
+    pub fn probe_answered(&mut self, venv_path: &PathBuf, id: &RpcId) -> bool {
+        if self
+            .probes
+            .get(venv_path)
+            .is_some_and(|probe| probe.id == *id)
+        {
+            self.probes.remove(venv_path);
+            return true;
+        }
+        false
+    }
+
+    /// Whether a probe sent to session `session` of `venv_path` is still unanswered
+    pub fn has_probe(&self, venv_path: &Path, session: u64) -> bool {
+        self.probes
+            .get(venv_path)
+            .is_some_and(|probe| probe.session == session)
+    }
+
+    /// How long the backend of `venv_path` has left its oldest request (or probe)
+    /// unanswered. None when nothing is outstanding, while the backend is warming up
+    /// (requests sent during indexing may legitimately take until it finishes), and while
+    /// it is suspended (it cannot answer until resumed).
+    ///
+    /// Requests allowed to run longer than the hang timeout (a longer per-method
+    /// timeout, or none) are not counted; the probe still catches a hang meanwhile.
+    /// Neither are late requests: the proxy answered them and cancelled them in the
+    /// backend, which need not answer a cancelled request.
+    pub fn unresponsive_for(
+        &self,
+        state: &ProxyState,
+        venv_path: &Path,
+        now: Instant,
+    ) -> Option<Duration> {
+        let inst = state.pool.get(venv_path)?;
+        if inst.is_warming() || inst.is_suspended() {
+            return None;
+        }
+        let session = inst.session;
+        let hang_timeout = state.config.hang_timeout();
+        let may_outlast_hang = |request: &RpcMessage| {
+            let timeout = request
+                .method
+                .as_deref()
+                .and_then(|method| state.config.request_timeout(method));
+            match (timeout, hang_timeout) {
+                (None, _) => true,
+                (Some(timeout), Some(hang)) => timeout > hang,
+                (Some(_), None) => false,
+            }
+        };
+
+        // Requests held in the warmup queue were never sent to the backend
+        let queued: Vec<&RpcId> = inst
+            .warmup_queue
+            .iter()
+            .filter_map(|msg| msg.id.as_ref())
+            .collect();
+        let requests = state
+            .pending_requests
+            .iter()
+            .filter(|(id, p)| {
+                p.venv_path == venv_path
+                    && p.backend_session == session
+                    && !queued.contains(id)
+                    && !may_outlast_hang(&p.request)
+            })
+            .map(|(_, p)| p.sent_at);
+        let probe = self
+            .probes
+            .get(venv_path)
+            .filter(|probe| probe.session == session)
+            .map(|probe| probe.sent_at);
+
+        oldest_outstanding(requests.chain(probe), now)
+    }
+}
+
+/// Age of the oldest request in `sent_at` (None when nothing is outstanding)
+pub(crate) fn oldest_outstanding(
+    sent_at: impl IntoIterator<Item = Instant>,
+    now: Instant,
+) -> Option<Duration> {
+    sent_at
+        .into_iter()
+        .min()
+        .map(|oldest| now.saturating_duration_since(oldest))
+}
+
+impl super::LspProxy {
+    /// Detect hung backends and send due liveness probes.
+    ///
+    /// A backend is hung when its oldest outstanding request (or probe) is older than
+    /// `hang_timeout_secs`. Hung backends are killed and recycled like crashed ones.
+    pub(crate) async fn check_backend_health(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let hang_timeout = self.state.config.hang_timeout();
+        let now = Instant::now();
+        self.state.expire_late_requests(now, CANCEL_GRACE);
+
+        for venv_path in self.state.pool.backends_keys() {
+            let Some(session) = self.state.pool.get(&venv_path).map(|inst| inst.session) else {
+                continue;
+            };
+            let Some(age) = self.health.unresponsive_for(&self.state, &venv_path, now) else {
+                continue;
+            };
+            if hang_timeout.is_some_and(|timeout| age >= timeout) {
+                tracing::error!(
+                    venv = %venv_path.display(),
+                    session = session,
+                    unresponsive_secs = age.as_secs(),
+                    "Backend is not responding, recycling it"
+                );
+                self.recycle_hung_backend(&venv_path, session, age, client_writer)
+                    .await?;
+            }
+        }
+
+        self.send_due_probes(now).await;
+        Ok(())
+    }
+
+    /// Send a liveness probe to every ready backend without one in flight,
+    /// once per `health_probe_interval_secs`
+    async fn send_due_probes(&mut self, now: Instant) {
+        let Some(interval) = self.state.config.health_probe_interval() else {
+            return;
+        };
+        if self
+            .health
+            .last_probe_round
+            .is_some_and(|last| now.duration_since(last) < interval)
+        {
+            return;
+        }
+        self.health.last_probe_round = Some(now);
+
+        for venv_path in self.state.pool.backends_keys() {
+            let Some(inst) = self.state.pool.get(&venv_path) else {
+                continue;
+            };
+            // Warming backends may legitimately be busy indexing;
+            // suspended ones are not expected to answer
+            if inst.is_warming()
+                || inst.is_suspended()
+                || self.health.has_probe(&venv_path, inst.session)
+            {
+                continue;
+            }
+
+            let id = self.state.alloc_backend_request_id();
+            let Some(inst) = self.state.pool.get_mut(&venv_path) else {
+                continue;
+            };
+            let probe = RpcMessage {
+                jsonrpc: "2.0".to_string(),
+                id: Some(id.clone()),
+                method: Some(PROBE_METHOD.to_string()),
+                params: None,
+                result: None,
+                error: None,
+            };
+            match inst.send(&probe).await {
+                Ok(()) => {
+                    tracing::trace!(venv = %venv_path.display(), id = ?id, "Sent liveness probe");
+                    self.health.probes.insert(
+                        venv_path.clone(),
+                        Probe {
+                            session: inst.session,
+                            id,
+                            sent_at: now,
+                        },
+                    );
+                }
+                Err(e) => {
+                    // A dead pipe surfaces as a crash through the reader task
+                    tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to send liveness probe");
+                }
+            }
+        }
+    }
+
+    /// Kill a hung backend, fail its pending requests, and respawn it with crash backoff
+    async fn recycle_hung_backend(
+        &mut self,
+        venv_path: &PathBuf,
+        session: u64,
+        unresponsive_for: Duration,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(mut instance) = self.state.pool.remove(venv_path) else {
+            return Ok(());
+        };
+        self.health.probes.remove(venv_path);
+
+        let message = format!(
+            "lsp-proxy: backend for {} did not respond for {}s and was restarted",
+            venv_path.display(),
+            unresponsive_for.as_secs()
+        );
+        self.cancel_pending_requests_for_backend(client_writer, venv_path, session, &message)
+            .await?;
+        self.clean_pending_backend_requests(venv_path, session);
+
+        // No graceful shutdown: a hung backend would not process it
+        instance.reader_task.abort();
+        if let Err(e) = instance.child.start_kill() {
+            tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to kill hung backend");
+        }
+
+        if !self.boot.contains(venv_path) {
+            self.schedule_crash_respawn(venv_path, client_writer).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_outstanding_age() {
+        let t0 = Instant::now();
+        let now = t0 + Duration::from_secs(40);
+        let sent = [t0 + Duration::from_secs(37), t0, now];
+        assert_eq!(oldest_outstanding(sent, now), Some(Duration::from_secs(40)));
+        assert_eq!(oldest_outstanding([], now), None);
+    }
+
+    #[test]
+    fn probe_answered_matches_venv_and_id() {
+        let venv = PathBuf::from("/r/a/.venv");
+        let mut health = HealthState::default();
+        health.probes.insert(
+            venv.clone(),
+            Probe {
+                session: 1,
+                id: RpcId::Number(7),
+                sent_at: Instant::now(),
+            },
+        );
+
+        assert!(!health.probe_answered(&venv, &RpcId::Number(8)));
+        assert!(!health.probe_answered(&PathBuf::from("/r/b/.venv"), &RpcId::Number(7)));
+        assert!(health.probe_answered(&venv, &RpcId::Number(7)));
+        assert!(!health.probe_answered(&venv, &RpcId::Number(7)));
+    }
+
+    /// A pool with one ready backend at /r/a/.venv and a `method` request sent to it
+    fn state_with_request(method: &str, sent_at: Instant) -> ProxyState {
+        use crate::backend::BackendKind;
+        use crate::backend_pool::BackendInstance;
+
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        state.pool.insert(BackendInstance::stub(
+            "/r/a/.venv",
+            BackendKind::Pyright,
+            Duration::ZERO,
+        ));
+        let request = RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(RpcId::Number(1)),
+            method: Some(method.to_string()),
+            params: None,
+            result: None,
+            error: None,
+        };
+        let sent = state.register_request(&request, Path::new("/r/a/.venv"), 1);
+        state
+            .pending_requests
+            .get_mut(&sent.id.unwrap())
+            .unwrap()
+            .sent_at = sent_at;
+        state
+    }
+
+    #[tokio::test]
+    async fn warming_backends_are_not_hung() {
+        use crate::backend_pool::WarmupState;
+
+        let venv = PathBuf::from("/r/a/.venv");
+        let sent_at = Instant::now();
+        let mut state = state_with_request("textDocument/hover", sent_at);
+        let health = HealthState::default();
+        let now = sent_at + Duration::from_secs(90);
+
+        assert_eq!(
+            health.unresponsive_for(&state, &venv, now),
+            Some(Duration::from_secs(90))
+        );
+
+        state.pool.get_mut(&venv).unwrap().warmup_state = WarmupState::Warming;
+        assert_eq!(health.unresponsive_for(&state, &venv, now), None);
+    }
+
+    #[tokio::test]
+    async fn suspended_backends_are_not_hung() {
+        let venv = PathBuf::from("/r/a/.venv");
+        let sent_at = Instant::now();
+        let mut state = state_with_request("textDocument/hover", sent_at);
+        let id = state.pending_request_id(&RpcId::Number(1)).unwrap();
+        state.remove_pending(&id);
+        let mut health = HealthState::default();
+        health.probes.insert(
+            venv.clone(),
+            Probe {
+                session: 1,
+                id: RpcId::Number(7),
+                sent_at,
+            },
+        );
+        let now = sent_at + Duration::from_secs(300);
+        assert!(health.has_probe(&venv, 1));
+        assert!(!health.has_probe(&venv, 2));
+        assert_eq!(
+            health.unresponsive_for(&state, &venv, now),
+            Some(Duration::from_secs(300))
+        );
+
+        state.pool.get_mut(&venv).unwrap().suspended_at = Some(sent_at);
+        assert_eq!(health.unresponsive_for(&state, &venv, now), None);
+    }
+
+    #[tokio::test]
+    async fn requests_outlasting_the_hang_timeout_are_not_counted() {
+        let venv = PathBuf::from("/r/a/.venv");
+        let sent_at = Instant::now();
+        let now = sent_at + Duration::from_secs(200);
+        let health = HealthState::default();
+
+        // workspace/symbol may run 300s, longer than the 120s hang timeout
+        let mut state = state_with_request("workspace/symbol", sent_at);
+        state
+            .config
+            .request_timeouts
+            .insert("workspace/symbol".to_string(), 300);
+        assert_eq!(health.unresponsive_for(&state, &venv, now), None);
+
+        // Without a timeout a request may run indefinitely
+        let mut state = state_with_request("textDocument/hover", sent_at);
+        state.config.request_timeout_secs = 0;
+        assert_eq!(health.unresponsive_for(&state, &venv, now), None);
+
+        let mut state = state_with_request("textDocument/hover", sent_at);
+        state
+            .config
+            .request_timeouts
+            .insert("textDocument/hover".to_string(), 100);
+        assert_eq!(
+            health.unresponsive_for(&state, &venv, now),
+            Some(Duration::from_secs(200))
+        );
+    }
+
+    #[tokio::test]
+    async fn timed_out_requests_do_not_count_toward_hang() {
+        let venv = PathBuf::from("/r/a/.venv");
+        let sent_at = Instant::now();
+        let mut state = state_with_request("textDocument/hover", sent_at);
+        let health = HealthState::default();
+
+        // Timed out after the default 60s: answered and cancelled, so the backend
+        // is not recycled at the 120s hang timeout for it
+        let id = state.pending_requests.keys().next().unwrap().clone();
+        state.mark_late(&id, sent_at + Duration::from_secs(60));
+        let now = sent_at + Duration::from_secs(130);
+        assert_eq!(health.unresponsive_for(&state, &venv, now), None);
+    }
+}
@@ -1,5 +1,8 @@
-use crate::backend::LspBackend;
-use crate::backend_pool::{spawn_reader_task, warmup_timeout, BackendInstance, WarmupState};
+use crate::backend::{BackendKind, LspBackend};
+use crate::backend_pool::{
+    spawn_reader_task, warmup_timeout_override, BackendInstance, WarmupState,
+    DEFAULT_WARMUP_TIMEOUT,
+};
 use crate::error::ProxyError;
 use crate::framing::LspFrameWriter;
 use crate::message::{RpcId, RpcMessage};
@@ -13,7 +16,7 @@ use tokio::time::Instant;
 /// 3. Send `initialized` notification
 ///
 /// Returns the initialize response from the backend.
-async fn perform_initialize_handshake(
+pub(super) async fn perform_initialize_handshake(
     backend: &mut LspBackend,
     init_params: Value,
     venv: &Path,
@@ -56,8 +59,14 @@ async fn perform_initialize_handshake(
                                     )),
                                 ));
                             }
+                            let server_info = msg
+                                .result
+                                .as_ref()
+                                .and_then(|r| r.get("serverInfo"))
+                                .map(|info| info.to_string());
                             tracing::info!(
                                 venv = %venv.display(),
+                                server_info = ?server_info,
                                 "Received initialize response from backend"
                             );
                             break msg;
@@ -103,8 +112,35 @@ async fn perform_initialize_handshake(
 }
 
 impl super::LspProxy {
+    /// Backend kind of a venv, resolved on first use and cached. `project_dir` is the
+    /// project asking for it, used only for envs outside the tree (see
+    /// `venv::resolve_backend_kind`).
+    pub(crate) fn backend_kind_for(
+        &mut self,
+        venv: &Path,
+        project_dir: Option<&Path>,
+    ) -> BackendKind {
+        if let Some(kind) = self.state.venv_kinds.get(venv) {
+            return *kind;
+        }
+        let kind = crate::venv::resolve_backend_kind(
+            venv,
+            project_dir,
+            self.state.git_toplevel.as_deref(),
+            &self.state.config,
+            self.state.backend_kind,
+        );
+        tracing::debug!(
+            venv = %venv.display(),
+            backend = kind.display_name(),
+            "Backend kind selected for venv"
+        );
+        self.state.venv_kinds.insert(venv.to_path_buf(), kind);
+        kind
+    }
+
     /// Extract cached initialize params, returning an error if not available.
-    fn cached_init_params(&self) -> Result<Value, ProxyError> {
+    pub(crate) fn cached_init_params(&self) -> Result<Value, ProxyError> {
         self.state
             .client_initialize
             .as_ref()
@@ -124,43 +160,33 @@ impl super::LspProxy {
         perform_initialize_handshake(backend, init_params, venv).await
     }
 
-    /// Create a new backend, initialize it, split it, and return a BackendInstance.
-    /// Does NOT insert into the pool — caller is responsible for that.
-    pub(crate) async fn create_backend_instance(
-        &mut self,
+    /// Split an initialized backend, start its reader task, and wrap it in a
+    /// BackendInstance (warming up). Does NOT insert into the pool.
+    pub(crate) fn build_backend_instance(
+        &self,
+        backend: LspBackend,
         venv: &Path,
-        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
-    ) -> Result<BackendInstance, ProxyError> {
-        let session = self.state.pool.next_session_id();
-
-        tracing::info!(
-            session = session,
-            venv = %venv.display(),
-            "Creating new backend instance"
-        );
-
-        // 1. Spawn
-        let mut backend = LspBackend::spawn(self.state.backend_kind, Some(venv)).await?;
-
-        // 2. Initialize handshake
-        let init_params = self.cached_init_params()?;
-        perform_initialize_handshake(&mut backend, init_params, venv).await?;
-        tracing::info!(session = session, venv = %venv.display(), "Backend initialized");
-
-        // 3. Document restoration for this venv
-        self.restore_documents_to_backend(&mut backend, venv, session, client_writer)
-            .await?;
-
-        // 4. Split and create instance
+        kind: BackendKind,
+        session: u64,
+    ) -> BackendInstance {
         let parts = backend.into_split();
         let tx = self.state.pool.msg_sender();
         let reader_task = spawn_reader_task(parts.reader, tx, venv.to_path_buf(), session);
 
-        let timeout = warmup_timeout();
-        Ok(BackendInstance {
+        // Explicit override > tuned from this venv's observed warmups > default
+        let timeout = warmup_timeout_override()
+            .or_else(|| self.history.warmup_timeout(venv))
+            .unwrap_or(DEFAULT_WARMUP_TIMEOUT);
+        tracing::debug!(
+            venv = %venv.display(),
+            warmup_timeout_ms = timeout.as_millis() as u64,
+            "Warmup timeout"
+        );
+        BackendInstance {
             writer: parts.writer,
             child: parts.child,
             venv_path: venv.to_path_buf(),
+            kind,
             session,
             last_used: Instant::now(),
             reader_task,
@@ -171,8 +197,11 @@ impl super::LspProxy {
                 WarmupState::Warming
             },
             warmup_deadline: Instant::now() + timeout,
+            warmup_started: Some(Instant::now()),
             warmup_queue: Vec::new(),
-        })
+            rss_bytes: None,
+            suspended_at: None,
+        }
     }
 
     /// Restore documents belonging to a venv to a backend
@@ -1,23 +1,48 @@
 mod backend_dispatch;
+mod boot;
 mod client_dispatch;
+mod control;
+mod crash_recovery;
 mod diagnostics;
 mod document;
+mod fanout;
+mod health;
 mod initialization;
 mod pool_management;
+mod prewarm;
+mod routing;
+mod timeouts;
+mod venv_watch;
+mod watched_files;
 
-use crate::backend::{BackendKind, LspBackend};
-use crate::error::ProxyError;
+use crate::backend::{self, BackendKind, LspBackend};
+use crate::config::{Config, ConfigOverrides};
+use crate::error::{ConfigError, ProxyError};
 use crate::framing::{LspFrameReader, LspFrameWriter};
+use crate::history::VenvHistory;
 use crate::state::ProxyState;
 use crate::venv;
+use crate::watcher::WatchEvent;
 use std::path::PathBuf;
 use std::time::Duration;
 use tokio::io::{stdin, stdout};
+use tokio::sync::mpsc;
 use tokio::time::{Instant, MissedTickBehavior};
 
 pub struct LspProxy {
     state: ProxyState,
     backend_ttl: Option<Duration>,
+    config_overrides: ConfigOverrides,
+    venv_watch: venv_watch::VenvWatchState,
+    boot: boot::BootState,
+    crash_recovery: crash_recovery::CrashRecoveryState,
+    health: health::HealthState,
+    request_timeouts: timeouts::RequestTimeouts,
+    history: VenvHistory,
+    prewarm: prewarm::PrewarmState,
+    fanout: fanout::FanoutState,
+    /// Error in `.typemux.toml` (defaults are used), reported to the client once initialized
+    config_error: Option<ConfigError>,
 }
 
 impl LspProxy {
@@ -25,10 +50,21 @@ impl LspProxy {
         backend_kind: BackendKind,
         max_backends: usize,
         backend_ttl: Option<Duration>,
+        config_overrides: ConfigOverrides,
     ) -> Self {
         Self {
             state: ProxyState::new(backend_kind, max_backends, backend_ttl),
             backend_ttl,
+            config_overrides,
+            venv_watch: venv_watch::VenvWatchState::default(),
+            boot: boot::BootState::default(),
+            crash_recovery: crash_recovery::CrashRecoveryState::default(),
+            health: health::HealthState::default(),
+            request_timeouts: timeouts::RequestTimeouts::default(),
+            history: VenvHistory::default(),
+            prewarm: prewarm::PrewarmState::default(),
+            fanout: fanout::FanoutState::default(),
+            config_error: None,
         }
     }
 
@@ -48,17 +84,46 @@ impl LspProxy {
         // Get and cache git toplevel
         self.state.git_toplevel = venv::get_git_toplevel(&cwd).await?;
 
+        // Load project config (.typemux.toml) from the git toplevel. A broken file must
+        // not take the editor's language server down: fall back to the defaults
+        self.state.config = match Config::load(self.state.git_toplevel.as_deref())
+            .and_then(|config| config.with_overrides(&self.config_overrides))
+        {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!(error = %e, "Invalid project config, using defaults");
+                self.config_error = Some(e);
+                Config::default().with_overrides(&self.config_overrides)?
+            }
+        };
+        self.history = VenvHistory::load(self.state.git_toplevel.as_deref());
+        for venv_path in &self.state.config.pinned_venvs {
+            tracing::info!(venv = %venv_path.display(), "Pinned venv");
+            self.state.pool.set_pinned(venv_path.clone(), true);
+        }
+
+        // Watch for venvs created/removed under the git toplevel and for package changes
+        let (watch_tx, mut watch_rx) = mpsc::channel::<WatchEvent>(64);
+        self.start_venv_watcher(watch_tx);
+
+        // Fail fast on backends that cannot be spawned (e.g. a configured command is missing)
+        backend::validate_backend_commands(&self.state.config, self.state.backend_kind)?;
+
         // Search for fallback venv
-        let fallback_venv = venv::find_fallback_venv(&cwd).await?;
+        let fallback_venv = venv::find_fallback_venv(&cwd, &self.state.config).await?;
 
         // Pre-spawn backend if fallback venv found (but don't insert into pool yet —
         // wait for client's `initialize` to complete the handshake first)
-        let mut pending_initial_backend: Option<(LspBackend, PathBuf)> = if let Some(venv) =
+        let mut pending_initial_backend: Option<(LspBackend, PathBuf, BackendKind)> = if let Some(
+            venv,
+        ) =
             fallback_venv
         {
-            tracing::info!(venv = %venv.display(), "Using fallback .venv, pre-spawning backend");
-            let backend = LspBackend::spawn(self.state.backend_kind, Some(&venv)).await?;
-            Some((backend, venv))
+            let kind = self.backend_kind_for(&venv, Some(&cwd));
+            tracing::info!(venv = %venv.display(), backend = kind.display_name(), "Using fallback .venv, pre-spawning backend");
+            let command = self.state.config.backend_command(kind)?;
+            let backend = LspBackend::spawn(kind, &command, Some(&venv)).await?;
+            Some((backend, venv, kind))
         } else {
             tracing::warn!("No fallback .venv found, starting with empty pool");
             None
@@ -66,20 +131,45 @@ impl LspProxy {
 
         let mut didopen_count = 0;
 
-        // TTL sweep timer: checks every 60 seconds for expired backends
+        // TTL sweep timer: checks every 60 seconds for expired and idle backends
+        let ttl_sweep_enabled =
+            self.backend_ttl.is_some() || self.state.config.suspend_ttl().is_some();
         let mut ttl_interval = tokio::time::interval(std::time::Duration::from_secs(60));
         ttl_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         // Consume the first immediate tick so the first real tick fires after 60s
         ttl_interval.tick().await;
 
+        // Health check timer: hang detection and liveness probes
+        let health_enabled = self.state.config.hang_timeout().is_some()
+            || self.state.config.health_probe_interval().is_some();
+        let mut health_interval = tokio::time::interval(health::HEALTH_CHECK_INTERVAL);
+        health_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        health_interval.tick().await;
+
+        // Memory sampler: evicts LRU backends while over the memory budget
+        let memory_budget_enabled = self.state.config.memory_budget_bytes().is_some();
+        let mut memory_interval = tokio::time::interval(pool_management::MEMORY_SAMPLE_INTERVAL);
+        memory_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        memory_interval.tick().await;
+
         loop {
-            // Compute warmup deadline before entering select! to avoid borrow conflicts
+            // Compute deadlines before entering select! to avoid borrow conflicts
             let warmup_deadline = self.state.pool.nearest_warmup_deadline();
+            let watch_deadline = self.venv_watch.next_deadline();
+            let respawn_deadline = self.crash_recovery.next_deadline();
+            let request_deadline = self.request_timeouts.next_deadline();
+            let fanout_deadline = self.fanout.next_deadline();
 
             tokio::select! {
                 // Messages from client
                 result = client_reader.read_message() => {
-                    let msg = result?;
+                    let msg = match result {
+                        Ok(msg) => msg,
+                        Err(e) => {
+                            self.history.flush();
+                            return Err(e.into());
+                        }
+                    };
                     let method = msg.method_name();
 
                     tracing::debug!(
@@ -95,16 +185,18 @@ impl LspProxy {
                             self.dispatch_initialize(&msg, &mut pending_initial_backend, &mut client_writer).await?;
                         }
                         Some("initialized") => {
-                            self.dispatch_initialized().await?;
+                            self.dispatch_initialized(&mut client_writer).await?;
                         }
                         Some("shutdown") => {
                             self.dispatch_shutdown(&msg, &mut client_writer).await?;
                         }
                         Some("exit") => {
                             tracing::info!("Received exit notification, terminating proxy");
+                            self.history.flush();
                             return Ok(());
                         }
                         _ if msg.is_response() => {
+                            #[allow(clippy::collapsible_match)]
                             if self.dispatch_client_response(&msg).await? {
                                 continue;
                             }
@@ -114,6 +206,7 @@ impl LspProxy {
                         Some("textDocument/didOpen") => {
                             didopen_count += 1;
                             self.handle_did_open(&msg, didopen_count, &mut client_writer).await?;
+                            self.sync_venv_watcher();
                         }
                         Some("textDocument/didChange") => {
                             self.handle_did_change(&msg).await?;
@@ -122,7 +215,7 @@ impl LspProxy {
                                 if let Some(venv_path) = self.venv_for_uri(&url) {
                                     if let Some(inst) = self.state.pool.get_mut(&venv_path) {
                                         inst.last_used = Instant::now();
-                                        if let Err(e) = inst.writer.write_message(&msg).await {
+                                        if let Err(e) = inst.send(&msg).await {
                                             tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to forward didChange");
                                         }
                                     }
@@ -135,19 +228,29 @@ impl LspProxy {
                                 .and_then(|url| self.venv_for_uri(&url));
 
                             self.handle_did_close(&msg).await?;
+                            self.sync_venv_watcher();
 
                             // Forward to appropriate backend
                             if let Some(venv_path) = venv_for_close {
                                 if let Some(inst) = self.state.pool.get_mut(&venv_path) {
                                     inst.last_used = Instant::now();
-                                    if let Err(e) = inst.writer.write_message(&msg).await {
+                                    if let Err(e) = inst.send(&msg).await {
                                         tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to forward didClose");
                                     }
                                 }
                             }
                         }
+                        Some(control::PIN_METHOD) if msg.is_request() => {
+                            self.dispatch_pin(&msg, &mut client_writer).await?;
+                        }
+                        Some(control::STATUS_METHOD) if msg.is_request() => {
+                            self.dispatch_status(&msg, &mut client_writer).await?;
+                        }
                         Some("$/cancelRequest") => {
-                            self.dispatch_cancel_request(&msg).await?;
+                            self.dispatch_cancel_request(&msg, &mut client_writer).await?;
+                        }
+                        Some("workspace/didChangeWatchedFiles") => {
+                            self.dispatch_did_change_watched_files(&msg).await?;
                         }
                         _ if msg.is_request() => {
                             self.dispatch_client_request(&msg, &mut client_writer).await?;
@@ -164,9 +267,30 @@ impl LspProxy {
                     self.dispatch_backend_message(backend_msg, &mut client_writer).await?;
                 }
 
-                // TTL-based auto-eviction sweep
-                _ = ttl_interval.tick(), if self.backend_ttl.is_some() => {
+                // Venv discovery finished: pre-warm likely-next backends
+                Some(discovered) = self.prewarm.rx.recv() => {
+                    self.prewarm_backends(discovered, &mut client_writer).await?;
+                }
+
+                // Background backend boot finished (or failed)
+                Some(done) = self.boot.rx.recv() => {
+                    self.handle_boot_complete(done, &mut client_writer).await?;
+                }
+
+                // TTL-based auto-eviction and idle suspension sweep
+                _ = ttl_interval.tick(), if ttl_sweep_enabled => {
                     self.evict_expired_backends(&mut client_writer).await?;
+                    self.suspend_idle_backends();
+                }
+
+                // Hang detection and liveness probes
+                _ = health_interval.tick(), if health_enabled => {
+                    self.check_backend_health(&mut client_writer).await?;
+                }
+
+                // Backend memory over budget: evict LRU backends
+                _ = memory_interval.tick(), if memory_budget_enabled => {
+                    self.enforce_memory_budget(&mut client_writer).await?;
                 }
 
                 // Warmup timeout: fail-open transition for warming backends
@@ -178,7 +302,54 @@ impl LspProxy {
                 } => {
                     self.expire_warmup_backends(&mut client_writer).await?;
                 }
+
+                // Crash backoff elapsed: respawn crashed backends
+                _ = async {
+                    match respawn_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                } => {
+                    self.respawn_crashed_backends(&mut client_writer).await?;
+                }
+
+                // Request timeout: answer overdue requests with an error
+                _ = async {
+                    match request_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                } => {
+                    self.expire_timed_out_requests(&mut client_writer).await?;
+                }
+
+                // Fan-out deadline: answer with the results gathered so far
+                _ = async {
+                    match fanout_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                } => {
+                    self.expire_fanouts(&mut client_writer).await?;
+                }
+
+                // Venv created/removed or packages changed: (re)start a debounce window
+                Some(event) = watch_rx.recv(), if self.venv_watch.is_active() => {
+                    self.handle_watch_event(event);
+                }
+
+                // Debounce elapsed: re-resolve open documents / restart backends
+                _ = async {
+                    match watch_deadline {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        None => std::future::pending::<()>().await,
+                    }
+                } => {
+                    self.handle_watch_deadlines(&mut client_writer).await?;
+                }
             }
+
+            self.sync_venv_watcher_if_pool_changed();
         }
     }
 }
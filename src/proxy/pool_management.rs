@@ -1,12 +1,19 @@
-use crate::backend_pool::shutdown_backend_instance;
+use crate::backend_pool::{shutdown_backend_instance, BackendInstance};
 use crate::error::ProxyError;
 use crate::framing::LspFrameWriter;
 use crate::message::{RpcId, RpcMessage};
 use crate::venv;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Interval between backend memory samples when a memory budget is set
+pub(crate) const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Bytes per MiB (memory budget logging)
+const MIB: u64 = 1024 * 1024;
 
 impl super::LspProxy {
-    /// Ensure a backend for the given URI's venv is in the pool.
+    /// Ensure a backend for the given URI's venv is in the pool or booting.
     /// Returns Some(venv_path) if a backend is available, None if no venv found.
     pub(crate) async fn ensure_backend_in_pool(
         &mut self,
@@ -19,7 +26,12 @@ impl super::LspProxy {
             doc.venv.clone()
         } else {
             tracing::debug!(uri = %url, "URI not in cache, searching venv");
-            venv::find_venv(file_path, self.state.git_toplevel.as_deref()).await?
+            venv::find_venv(
+                file_path,
+                self.state.git_toplevel.as_deref(),
+                &self.state.config,
+            )
+            .await?
         };
 
         let target_venv = match target_venv {
@@ -32,16 +44,10 @@ impl super::LspProxy {
             return Ok(Some(target_venv));
         }
 
-        // Need to create a new backend. Evict if full.
-        if self.state.pool.is_full() {
-            self.evict_lru_backend(client_writer).await?;
-        }
-
-        // Create backend instance
-        let instance = self
-            .create_backend_instance(&target_venv, client_writer)
+        // Boot a new backend (evicting if full); requests are queued until it is ready
+        let kind = self.backend_kind_for(&target_venv, file_path.parent());
+        self.start_backend_boot(&target_venv, kind, false, client_writer)
             .await?;
-        self.state.pool.insert(target_venv.clone(), instance);
 
         Ok(Some(target_venv))
     }
@@ -51,43 +57,127 @@ impl super::LspProxy {
         &mut self,
         client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
     ) -> Result<(), ProxyError> {
+        if let Some(venv_to_evict) = self.lru_eviction_candidate() {
+            tracing::info!(
+                venv = %venv_to_evict.display(),
+                pool_size = self.state.pool.len(),
+                "Evicting LRU backend"
+            );
+
+            self.teardown_backend(&venv_to_evict, client_writer).await?;
+        }
+
+        Ok(())
+    }
+
+    /// LRU backend, preferring backends with no pending requests
+    fn lru_eviction_candidate(&self) -> Option<PathBuf> {
         let pending_requests = &self.state.pending_requests;
-        let lru_venv = self.state.pool.lru_venv(|venv, session| {
+        self.state.pool.lru_venv(|venv, session| {
             pending_requests
                 .values()
                 .filter(|p| p.venv_path == *venv && p.backend_session == session)
                 .count()
-        });
+        })
+    }
+
+    /// Sample backend RSS and evict LRU backends until the total is within
+    /// `memory_budget_mb`. The last remaining backend is never evicted.
+    pub(crate) async fn enforce_memory_budget(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(budget) = self.state.config.memory_budget_bytes() else {
+            return Ok(());
+        };
+        let mut total = self.state.pool.sample_total_rss();
+        tracing::debug!(
+            total_mb = total / MIB,
+            budget_mb = budget / MIB,
+            pool_size = self.state.pool.len(),
+            "Sampled backend memory"
+        );
 
-        if let Some(venv_to_evict) = lru_venv {
+        let pending_requests = &self.state.pending_requests;
+        let evictions = self
+            .state
+            .pool
+            .over_budget_evictions(total, budget, |venv, session| {
+                pending_requests
+                    .values()
+                    .filter(|p| p.venv_path == *venv && p.backend_session == session)
+                    .count()
+            });
+
+        let mut reclaimed = 0;
+        for (venv_path, rss) in evictions {
+            self.teardown_backend(&venv_path, client_writer).await?;
+            total = total.saturating_sub(rss);
+            reclaimed += rss;
             tracing::info!(
-                venv = %venv_to_evict.display(),
-                pool_size = self.state.pool.len(),
-                "Evicting LRU backend"
+                venv = %venv_path.display(),
+                rss_mb = rss / MIB,
+                total_mb = total / MIB,
+                budget_mb = budget / MIB,
+                "Evicted backend over memory budget"
             );
+        }
+        if reclaimed > 0 {
+            tracing::info!(
+                reclaimed_mb = reclaimed / MIB,
+                total_mb = total / MIB,
+                budget_mb = budget / MIB,
+                "Reclaimed backend memory"
+            );
+        }
+        Ok(())
+    }
 
-            if let Some(instance) = self.state.pool.remove(&venv_to_evict) {
-                let evict_session = instance.session;
+    /// Remove a backend from the pool and shut it down gracefully:
+    /// cancel its pending requests, drop its pending backend→client requests,
+    /// and clear diagnostics of the documents it served.
+    pub(crate) async fn teardown_backend(
+        &mut self,
+        venv_path: &PathBuf,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(instance) = self.state.pool.remove(venv_path) else {
+            return Ok(());
+        };
+        // A venv started again later resolves its backend kind afresh
+        self.state.venv_kinds.remove(venv_path);
+        self.shutdown_removed_backend(instance, client_writer).await
+    }
 
-                // Cancel pending requests for this backend
-                self.cancel_pending_requests_for_backend(
-                    client_writer,
-                    &venv_to_evict,
-                    evict_session,
-                )
-                .await?;
+    /// Shut down a backend that is no longer in the pool: cancel its pending
+    /// requests, drop its pending backend→client requests, and clear diagnostics
+    /// of the documents it served.
+    pub(crate) async fn shutdown_removed_backend(
+        &mut self,
+        instance: BackendInstance,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let venv_path = instance.venv_path.clone();
+        let session = instance.session;
 
-                // Clean up pending_backend_requests for this backend
-                self.clean_pending_backend_requests(&venv_to_evict, evict_session);
+        // Cancel pending requests for this backend
+        self.cancel_pending_requests_for_backend(
+            client_writer,
+            &venv_path,
+            session,
+            "Request cancelled due to backend eviction",
+        )
+        .await?;
 
-                // Clear diagnostics for documents under this venv
-                self.clear_diagnostics_for_venv(&venv_to_evict, client_writer)
-                    .await;
+        // Clean up pending_backend_requests for this backend
+        self.clean_pending_backend_requests(&venv_path, session);
 
-                // Shutdown
-                shutdown_backend_instance(instance);
-            }
-        }
+        // Clear diagnostics for documents under this venv
+        self.clear_diagnostics_for_venv(&venv_path, client_writer)
+            .await;
+
+        // Shutdown
+        shutdown_backend_instance(instance);
 
         Ok(())
     }
@@ -147,25 +237,96 @@ impl super::LspProxy {
                 "Evicting expired backend (TTL)"
             );
 
-            if let Some(instance) = self.state.pool.remove(&venv_path) {
-                let evict_session = instance.session;
+            self.teardown_backend(&venv_path, client_writer).await?;
+        }
 
-                self.cancel_pending_requests_for_backend(client_writer, &venv_path, evict_session)
-                    .await?;
+        Ok(())
+    }
 
-                self.clean_pending_backend_requests(&venv_path, evict_session);
+    /// Suspend (SIGSTOP) ready backends idle for `suspend_ttl_secs`.
+    /// Skips backends with pending requests in either direction; pinned backends
+    /// are never suspended. A suspended backend is resumed by its next message.
+    pub(crate) fn suspend_idle_backends(&mut self) {
+        let Some(suspend_ttl) = self.state.config.suspend_ttl() else {
+            return;
+        };
 
-                self.clear_diagnostics_for_venv(&venv_path, client_writer)
-                    .await;
+        for venv_path in self.state.pool.idle_venvs(suspend_ttl) {
+            let Some(inst) = self.state.pool.get_mut(&venv_path) else {
+                continue;
+            };
+            let session = inst.session;
+            let has_pending = self
+                .state
+                .pending_requests
+                .values()
+                .any(|p| p.venv_path == venv_path && p.backend_session == session)
+                || self
+                    .state
+                    .pending_backend_requests
+                    .values()
+                    .any(|p| p.venv_path == venv_path && p.session == session);
+            if has_pending {
+                tracing::debug!(venv = %venv_path.display(), "Skipping suspension: has pending requests");
+                continue;
+            }
+            // A stopped backend could not answer them, and would look hung once resumed
+            let owes_response = self
+                .state
+                .late_requests
+                .values()
+                .any(|late| late.venv_path == venv_path && late.backend_session == session)
+                || self.health.has_probe(&venv_path, session);
+            if owes_response {
+                tracing::debug!(venv = %venv_path.display(), "Skipping suspension: owes a late or probe response");
+                continue;
+            }
 
-                shutdown_backend_instance(instance);
+            match inst.suspend() {
+                Ok(()) => tracing::info!(
+                    venv = %venv_path.display(),
+                    session = session,
+                    idle_secs = inst.last_used.elapsed().as_secs(),
+                    "Suspended idle backend"
+                ),
+                Err(e) => tracing::warn!(
+                    venv = %venv_path.display(),
+                    error = ?e,
+                    "Failed to suspend idle backend"
+                ),
             }
         }
+    }
 
+    /// Restart a pooled backend in place (e.g. after its venv's packages changed).
+    ///
+    /// The replacement boots in the background while the old backend keeps serving;
+    /// once initialized (with open documents restored) it is swapped in and the old
+    /// session's in-flight requests are replayed to it, so the client sees no
+    /// failures. If the replacement fails to start, the old one keeps running.
+    pub(crate) async fn restart_backend(
+        &mut self,
+        venv_path: &Path,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let Some(kind) = self.state.pool.get(venv_path).map(|inst| inst.kind) else {
+            return Ok(());
+        };
+        if let Err(e) = self
+            .start_backend_boot(venv_path, kind, true, client_writer)
+            .await
+        {
+            tracing::error!(
+                venv = %venv_path.display(),
+                error = ?e,
+                "Failed to start replacement backend, keeping the old one"
+            );
+        }
         Ok(())
     }
 
-    /// Handle backend crash: remove from pool, cancel pending, clean up
+    /// Handle backend crash: remove from pool, cancel pending, clean up,
+    /// and schedule a respawn (or quarantine the venv if it keeps crashing)
     pub(crate) async fn handle_backend_crash(
         &mut self,
         venv_path: &PathBuf,
@@ -196,8 +357,13 @@ impl super::LspProxy {
 
         if let Some(instance) = self.state.pool.remove(venv_path) {
             // Cancel pending requests
-            self.cancel_pending_requests_for_backend(client_writer, venv_path, session)
-                .await?;
+            self.cancel_pending_requests_for_backend(
+                client_writer,
+                venv_path,
+                session,
+                "Request cancelled due to backend crash",
+            )
+            .await?;
 
             // Clean up pending_backend_requests
             self.clean_pending_backend_requests(venv_path, session);
@@ -211,20 +377,25 @@ impl super::LspProxy {
                 session = session,
                 "Backend removed from pool after crash"
             );
+
+            // A replacement that is already booting takes over on its own
+            if !self.boot.contains(venv_path) {
+                self.schedule_crash_respawn(venv_path, client_writer).await;
+            }
         }
 
         Ok(())
     }
 
-    /// Cancel pending requests for a specific backend (identified by venv_path + session)
+    /// Cancel pending requests for a specific backend (identified by venv_path + session),
+    /// answering each with `reason`
     pub(crate) async fn cancel_pending_requests_for_backend(
         &mut self,
         client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
         venv_path: &PathBuf,
         session: u64,
+        reason: &str,
     ) -> Result<(), ProxyError> {
-        const REQUEST_CANCELLED: i64 = -32800;
-
         let to_cancel: Vec<RpcId> = self
             .state
             .pending_requests
@@ -234,23 +405,20 @@ impl super::LspProxy {
             .collect();
 
         for id in to_cancel {
-            self.state.pending_requests.remove(&id);
-            let msg = RpcMessage {
-                jsonrpc: "2.0".to_string(),
-                id: Some(id.clone()),
-                method: None,
-                params: None,
-                result: None,
-                error: Some(crate::message::RpcError {
-                    code: REQUEST_CANCELLED,
-                    message: "Request cancelled due to backend eviction".to_string(),
-                    data: None,
-                }),
+            let Some(pending) = self.state.remove_pending(&id) else {
+                continue;
             };
+            let msg = RpcMessage::cancelled_response(pending.client_id.clone(), reason);
             client_writer.write_message(&msg).await?;
-            tracing::info!(id = ?id, venv = %venv_path.display(), session = session, "Cancelled pending request");
+            tracing::info!(id = ?pending.client_id, venv = %venv_path.display(), session = session, "Cancelled pending request");
         }
 
+        self.state.forget_late_requests(venv_path, session);
+
+        // Fan-out requests complete without this backend's answer
+        self.fanout_backend_gone(venv_path, session, client_writer)
+            .await?;
+
         Ok(())
     }
 
@@ -0,0 +1,91 @@
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::venv;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+/// Venvs discovered under the git toplevel, delivered from a blocking task
+pub(crate) struct PrewarmState {
+    tx: mpsc::Sender<Vec<PathBuf>>,
+    pub rx: mpsc::Receiver<Vec<PathBuf>>,
+    started: bool,
+}
+
+impl Default for PrewarmState {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel(1);
+        Self {
+            tx,
+            rx,
+            started: false,
+        }
+    }
+}
+
+impl super::LspProxy {
+    /// Start venv discovery in the background (once per session, when pre-warming is enabled)
+    pub(crate) fn start_prewarm_discovery(&mut self) {
+        if !self.state.config.prewarm || self.prewarm.started {
+            return;
+        }
+        let Some(toplevel) = self.state.git_toplevel.clone() else {
+            tracing::debug!("No git toplevel, skipping pre-warm");
+            return;
+        };
+        self.prewarm.started = true;
+
+        let venv_dirs = self.state.config.venv_dirs.clone();
+        let venv_markers = self.state.config.venv_markers.clone();
+        let tx = self.prewarm.tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let venvs = venv::discover_venvs(&toplevel, &venv_dirs, &venv_markers);
+            tracing::info!(
+                toplevel = %toplevel.display(),
+                found = venvs.len(),
+                "Discovered venvs for pre-warming"
+            );
+            let _ = tx.blocking_send(venvs);
+        });
+    }
+
+    /// Boot backends for the most used discovered venvs (see `VenvHistory::prewarm_order`),
+    /// up to `prewarm_budget`.
+    /// Pre-warming only fills free pool slots; it never evicts a backend.
+    pub(crate) async fn prewarm_backends(
+        &mut self,
+        discovered: Vec<PathBuf>,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let free_slots = self.state.pool.free_slots(self.unpinned_booting_count());
+        let budget = self.state.config.prewarm_budget.min(free_slots);
+
+        let candidates: Vec<PathBuf> = self
+            .history
+            .prewarm_order(&discovered)
+            .into_iter()
+            .filter(|venv| !self.state.pool.contains(venv) && !self.boot.contains(venv))
+            .take(budget)
+            .collect();
+        if candidates.is_empty() {
+            tracing::info!(
+                discovered = discovered.len(),
+                free_slots = free_slots,
+                "Nothing to pre-warm"
+            );
+            return Ok(());
+        }
+
+        for venv_path in candidates {
+            tracing::info!(venv = %venv_path.display(), "Pre-warming backend");
+            // Failures (quarantine, missing executable) only matter once the venv is used
+            let kind = self.backend_kind_for(&venv_path, None);
+            if let Err(e) = self
+                .start_backend_boot(&venv_path, kind, false, client_writer)
+                .await
+            {
+                tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to pre-warm backend");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::RpcMessage;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Params of URI-less requests that may carry a document URI, searched in order
+const URI_HINTS: &[(&str, &[&str])] = &[
+    ("workspace/executeCommand", &["arguments"]),
+    ("workspaceSymbol/resolve", &["location", "data"]),
+    ("codeAction/resolve", &["data", "edit"]),
+    ("codeLens/resolve", &["data"]),
+    ("completionItem/resolve", &["data"]),
+    ("documentLink/resolve", &["target", "data"]),
+    ("inlayHint/resolve", &["data"]),
+];
+
+/// How deep URI hints are searched inside a param (commands nest their arguments)
+const MAX_HINT_DEPTH: usize = 4;
+
+/// First `file:` URI in the params listed for the request's method in `URI_HINTS`
+pub(crate) fn request_uri_hint(msg: &RpcMessage) -> Option<url::Url> {
+    let method = msg.method_name()?;
+    let (_, fields) = URI_HINTS.iter().find(|(m, _)| *m == method)?;
+    let params = msg.params.as_ref()?;
+    fields
+        .iter()
+        .filter_map(|field| params.get(field))
+        .find_map(|value| find_file_uri(value, MAX_HINT_DEPTH))
+}
+
+/// Search `value` for a `file:` URI, preferring `uri` keys of objects
+fn find_file_uri(value: &Value, depth: usize) -> Option<url::Url> {
+    match value {
+        Value::String(s) if s.starts_with("file:") => {
+            url::Url::parse(s).ok().filter(|url| url.scheme() == "file")
+        }
+        Value::Array(items) if depth > 0 => {
+            items.iter().find_map(|item| find_file_uri(item, depth - 1))
+        }
+        Value::Object(map) if depth > 0 => map
+            .get("uri")
+            .and_then(|uri| find_file_uri(uri, 0))
+            .or_else(|| map.values().find_map(|v| find_file_uri(v, depth - 1))),
+        _ => None,
+    }
+}
+
+/// The client's primary workspace folder from its `initialize` params:
+/// the first of `workspaceFolders`, else `rootUri`, else `rootPath`
+pub(crate) fn primary_workspace_folder(initialize: &RpcMessage) -> Option<PathBuf> {
+    let params = initialize.params.as_ref()?;
+    let folder_uri = params
+        .get("workspaceFolders")
+        .and_then(|folders| folders.get(0))
+        .and_then(|folder| folder.get("uri"))
+        .or_else(|| params.get("rootUri"))
+        .and_then(|uri| uri.as_str());
+    if let Some(path) = folder_uri
+        .and_then(|uri| url::Url::parse(uri).ok())
+        .and_then(|url| url.to_file_path().ok())
+    {
+        return Some(path);
+    }
+    params
+        .get("rootPath")
+        .and_then(|p| p.as_str())
+        .map(PathBuf::from)
+}
+
+/// `find_venv` walks up from a file's parent, so a (possibly nonexistent) file inside
+/// `folder` makes the walk start at the folder itself
+fn file_in_folder(folder: &Path) -> PathBuf {
+    folder.join("__typemux_route__.py")
+}
+
+impl super::LspProxy {
+    /// Pick the venv for a request without a `textDocument`: a document URI found in
+    /// its params (`URI_HINTS`), else — with several backends active — the venv of the
+    /// client's primary workspace folder (or the working directory).
+    /// Boots the backend if needed. None leaves the request to the default handling.
+    pub(crate) async fn route_uri_less_request(
+        &mut self,
+        msg: &RpcMessage,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<Option<PathBuf>, ProxyError> {
+        let method = msg.method_name().unwrap_or_default();
+
+        if let Some(url) = request_uri_hint(msg) {
+            if let Ok(file_path) = url.to_file_path() {
+                match self
+                    .ensure_backend_in_pool(&url, &file_path, client_writer)
+                    .await
+                {
+                    Ok(Some(venv)) => {
+                        tracing::debug!(method = method, uri = %url, venv = %venv.display(), "Routed URI-less request by URI in params");
+                        return Ok(Some(venv));
+                    }
+                    Ok(None) => {
+                        tracing::debug!(method = method, uri = %url, "No venv for URI in params");
+                    }
+                    Err(e) => {
+                        tracing::warn!(method = method, uri = %url, error = ?e, "Failed to resolve venv for URI in params");
+                    }
+                }
+            }
+        }
+
+        // A single backend (or none) is handled by the caller without guessing
+        if self.state.pool.len() <= 1 {
+            return Ok(None);
+        }
+
+        let folder = match self
+            .state
+            .client_initialize
+            .as_ref()
+            .and_then(primary_workspace_folder)
+        {
+            Some(folder) => folder,
+            None => std::env::current_dir()?,
+        };
+        let Ok(folder_url) = url::Url::from_directory_path(&folder) else {
+            return Ok(None);
+        };
+        match self
+            .ensure_backend_in_pool(&folder_url, &file_in_folder(&folder), client_writer)
+            .await
+        {
+            Ok(Some(venv)) => {
+                tracing::info!(
+                    method = method,
+                    folder = %folder.display(),
+                    venv = %venv.display(),
+                    "Routed URI-less request to the workspace folder's venv"
+                );
+                Ok(Some(venv))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                tracing::warn!(method = method, folder = %folder.display(), error = ?e, "Failed to resolve workspace folder venv");
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(method: &str, params: Value) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(crate::message::RpcId::Number(1)),
+            method: Some(method.to_string()),
+            params: Some(params),
+            result: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn uri_hints_from_command_arguments_and_resolve_data() {
+        let command = request(
+            "workspace/executeCommand",
+            json!({
+                "command": "pyright.organizeimports",
+                "arguments": ["untitled:x", {"textDocument": {"uri": "file:///r/b/main.py"}}],
+            }),
+        );
+        assert_eq!(
+            request_uri_hint(&command).unwrap().as_str(),
+            "file:///r/b/main.py"
+        );
+
+        let resolve = request(
+            "workspaceSymbol/resolve",
+            json!({"name": "App", "kind": 5, "location": {"uri": "file:///r/a/app.py"}}),
+        );
+        assert_eq!(
+            request_uri_hint(&resolve).unwrap().as_str(),
+            "file:///r/a/app.py"
+        );
+
+        let code_lens = request(
+            "codeLens/resolve",
+            json!({"range": {}, "data": {"file": "file:///r/c/lib.py"}}),
+        );
+        assert_eq!(
+            request_uri_hint(&code_lens).unwrap().as_str(),
+            "file:///r/c/lib.py"
+        );
+
+        let diagnostic = request("workspace/diagnostic", json!({"previousResultIds": []}));
+        assert!(request_uri_hint(&diagnostic).is_none());
+    }
+
+    #[test]
+    fn primary_workspace_folder_prefers_workspace_folders() {
+        let init = request(
+            "initialize",
+            json!({
+                "rootUri": "file:///r/root",
+                "workspaceFolders": [{"uri": "file:///r/first", "name": "first"}, {"uri": "file:///r/second", "name": "second"}],
+            }),
+        );
+        assert_eq!(
+            primary_workspace_folder(&init),
+            Some(PathBuf::from("/r/first"))
+        );
+
+        let init = request(
+            "initialize",
+            json!({"rootUri": null, "rootPath": "/r/legacy", "workspaceFolders": null}),
+        );
+        assert_eq!(
+            primary_workspace_folder(&init),
+            Some(PathBuf::from("/r/legacy"))
+        );
+    }
+}
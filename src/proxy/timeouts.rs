@@ -0,0 +1,307 @@
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::{RpcId, RpcMessage};
+use crate::state::{PendingRequest, ProxyState};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How long a backend gets to answer a request the client cancelled before the
+/// proxy answers it with `RequestCancelled`
+pub(crate) const CANCEL_GRACE: Duration = Duration::from_secs(2);
+
+/// Deadlines of pending client requests, nearest first.
+///
+/// Entries are not removed when a request is answered; a due id whose request is
+/// gone (or was re-sent later) is skipped or rescheduled when it fires.
+#[derive(Default)]
+pub(crate) struct RequestTimeouts {
+    deadlines: BTreeMap<Instant, Vec<RpcId>>,
+}
+
+impl RequestTimeouts {
+    /// Start the clock for a request that was just registered (no-op without a timeout)
+    pub fn track(&mut self, id: &RpcId, timeout: Option<Duration>) {
+        if let Some(timeout) = timeout {
+            self.schedule(id.clone(), Instant::now() + timeout);
+        }
+    }
+
+    pub fn schedule(&mut self, id: RpcId, deadline: Instant) {
+        self.deadlines.entry(deadline).or_default().push(id);
+    }
+
+    /// Nearest deadline
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.keys().next().copied()
+    }
+
+    /// Remove and return ids whose deadline passed
+    pub fn take_due(&mut self, now: Instant) -> Vec<RpcId> {
+        let later = match now.checked_add(Duration::from_nanos(1)) {
+            Some(after_now) => self.deadlines.split_off(&after_now),
+            None => BTreeMap::new(),
+        };
+        std::mem::replace(&mut self.deadlines, later)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+}
+
+/// Where a client's `$/cancelRequest` for a pending request goes
+#[derive(Debug, PartialEq)]
+pub(crate) struct CancelTarget {
+    /// ID the owning backend knows the request by
+    pub backend_id: RpcId,
+    pub venv_path: PathBuf,
+    pub session: u64,
+    /// The owning backend session is still in the pool (else the cancel is dropped)
+    pub owner_alive: bool,
+}
+
+/// Resolve the owner of the request the client sent as `client_id` (None if not pending)
+pub(crate) fn cancel_target(state: &ProxyState, client_id: &RpcId) -> Option<CancelTarget> {
+    let backend_id = state.pending_request_id(client_id)?;
+    let pending = state.pending_requests.get(&backend_id)?;
+    let owner_alive = state
+        .pool
+        .get(&pending.venv_path)
+        .is_some_and(|inst| inst.session == pending.backend_session);
+    Some(CancelTarget {
+        backend_id,
+        venv_path: pending.venv_path.clone(),
+        session: pending.backend_session,
+        owner_alive,
+    })
+}
+
+/// What to do with a pending request whose deadline fired
+#[derive(Debug, PartialEq)]
+pub(crate) enum DueAction {
+    /// Not due (re-sent or cancelled since it was scheduled): check again then
+    Wait(Instant),
+    /// Cancelled by the client and the backend stayed silent for `CANCEL_GRACE`
+    AnswerCancelled,
+    /// Exceeded its request timeout
+    TimedOut(Duration),
+    /// Neither a timeout nor a cancel applies
+    Nothing,
+}
+
+pub(crate) fn due_action(
+    pending: &PendingRequest,
+    timeout: Option<Duration>,
+    now: Instant,
+) -> DueAction {
+    // Requests replayed after a restart get a fresh timeout
+    let timeout_deadline = timeout.map(|timeout| pending.sent_at + timeout);
+    let cancel_deadline = pending.cancelled_at.map(|at| at + CANCEL_GRACE);
+    let Some(deadline) = timeout_deadline.into_iter().chain(cancel_deadline).min() else {
+        return DueAction::Nothing;
+    };
+    if deadline > now {
+        DueAction::Wait(deadline)
+    } else if cancel_deadline.is_some_and(|deadline| deadline <= now) {
+        DueAction::AnswerCancelled
+    } else {
+        timeout.map_or(DueAction::Nothing, DueAction::TimedOut)
+    }
+}
+
+impl super::LspProxy {
+    /// Answer overdue client requests with `RequestCancelled` and cancel them in the backend.
+    /// Requests the client cancelled are answered once `CANCEL_GRACE` passed without a
+    /// response from the backend.
+    ///
+    /// A timed-out request that was already sent moves to `late_requests` until the
+    /// backend answers, so the late response is dropped instead of reaching the client twice.
+    pub(crate) async fn expire_timed_out_requests(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let now = Instant::now();
+        for id in self.request_timeouts.take_due(now) {
+            let Some(pending) = self.state.pending_requests.get(&id) else {
+                continue;
+            };
+            let method = pending
+                .request
+                .method_name()
+                .unwrap_or("unknown")
+                .to_string();
+            let timeout = self.state.config.request_timeout(&method);
+            let venv_path = pending.venv_path.clone();
+            let session = pending.backend_session;
+            let client_id = pending.client_id.clone();
+
+            let timeout = match due_action(pending, timeout, now) {
+                DueAction::Wait(deadline) => {
+                    self.request_timeouts.schedule(id, deadline);
+                    continue;
+                }
+                DueAction::Nothing => continue,
+                DueAction::AnswerCancelled => {
+                    // The backend already got the client's cancel
+                    tracing::info!(
+                        id = ?client_id,
+                        method = %method,
+                        venv = %venv_path.display(),
+                        "Backend did not answer cancelled request"
+                    );
+                    self.state.mark_late(&id, now);
+                    let response =
+                        RpcMessage::cancelled_response(client_id, "lsp-proxy: request cancelled");
+                    client_writer.write_message(&response).await?;
+                    continue;
+                }
+                DueAction::TimedOut(timeout) => timeout,
+            };
+
+            tracing::warn!(
+                id = ?client_id,
+                method = %method,
+                venv = %venv_path.display(),
+                timeout_secs = timeout.as_secs(),
+                "Request timed out"
+            );
+
+            // Still queued (boot or warmup): the backend never saw it
+            let was_queued = self.boot.cancel_queued_request(&venv_path, &id).is_some()
+                || self.state.pool.get_mut(&venv_path).is_some_and(|inst| {
+                    inst.session == session && inst.cancel_warmup_request(&id).is_some()
+                });
+            if was_queued {
+                self.state.remove_pending(&id);
+            } else {
+                self.state.mark_late(&id, now);
+                if let Some(inst) = self
+                    .state
+                    .pool
+                    .get_mut(&venv_path)
+                    .filter(|inst| inst.session == session)
+                {
+                    if let Err(e) = inst.send(&RpcMessage::cancel_notification(&id)).await {
+                        tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to cancel timed-out request in backend");
+                    }
+                }
+            }
+
+            let response = RpcMessage::cancelled_response(
+                client_id,
+                &format!(
+                    "lsp-proxy: {} timed out after {}s",
+                    method,
+                    timeout.as_secs()
+                ),
+            );
+            client_writer.write_message(&response).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::BackendKind;
+    use std::path::Path;
+
+    #[test]
+    fn take_due_returns_overdue_ids_in_order() {
+        let mut timeouts = RequestTimeouts::default();
+        let t0 = Instant::now();
+        timeouts.schedule(RpcId::Number(2), t0 + Duration::from_secs(2));
+        timeouts.schedule(RpcId::Number(1), t0 + Duration::from_secs(1));
+        timeouts.schedule(RpcId::Number(3), t0 + Duration::from_secs(3));
+
+        assert_eq!(timeouts.next_deadline(), Some(t0 + Duration::from_secs(1)));
+        assert_eq!(
+            timeouts.take_due(t0 + Duration::from_secs(2)),
+            vec![RpcId::Number(1), RpcId::Number(2)]
+        );
+        assert_eq!(timeouts.next_deadline(), Some(t0 + Duration::from_secs(3)));
+        assert!(timeouts.take_due(t0 + Duration::from_secs(2)).is_empty());
+    }
+
+    fn hover(id: i64) -> RpcMessage {
+        RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(RpcId::Number(id)),
+            method: Some("textDocument/hover".to_string()),
+            params: None,
+            result: None,
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn cancel_targets_only_the_owning_backend_session() {
+        use crate::backend_pool::BackendInstance;
+
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        state.pool.insert(BackendInstance::stub(
+            "/a/.venv",
+            BackendKind::Pyright,
+            Duration::ZERO,
+        ));
+        state.register_request(&hover(1), Path::new("/b/.venv"), 1);
+        let to_a = state.register_request(&hover(7), Path::new("/a/.venv"), 1);
+        let stale = state.register_request(&hover(8), Path::new("/a/.venv"), 0);
+
+        // Translated to the backend ID, owned by /a/.venv only
+        assert_eq!(
+            cancel_target(&state, &RpcId::Number(7)),
+            Some(CancelTarget {
+                backend_id: to_a.id.unwrap(),
+                venv_path: PathBuf::from("/a/.venv"),
+                session: 1,
+                owner_alive: true,
+            })
+        );
+        // The owning session is gone: nobody gets the cancel
+        let target = cancel_target(&state, &RpcId::Number(8)).unwrap();
+        assert_eq!(target.backend_id, stale.id.unwrap());
+        assert!(!target.owner_alive);
+        assert!(
+            !cancel_target(&state, &RpcId::Number(1))
+                .unwrap()
+                .owner_alive
+        );
+        assert_eq!(cancel_target(&state, &RpcId::Number(99)), None);
+    }
+
+    #[test]
+    fn cancelled_request_is_answered_after_grace() {
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        let sent = state.register_request(&hover(1), Path::new("/a/.venv"), 1);
+        let mut pending = state.pending_requests[&sent.id.unwrap()].clone();
+        let t0 = pending.sent_at;
+        let timeout = Some(Duration::from_secs(60));
+
+        assert_eq!(
+            due_action(&pending, timeout, t0),
+            DueAction::Wait(t0 + Duration::from_secs(60))
+        );
+        assert_eq!(due_action(&pending, None, t0), DueAction::Nothing);
+
+        // Cancelled: the backend gets CANCEL_GRACE to answer before the proxy does
+        pending.cancelled_at = Some(t0 + Duration::from_secs(10));
+        assert_eq!(
+            due_action(&pending, timeout, t0 + Duration::from_secs(11)),
+            DueAction::Wait(t0 + Duration::from_secs(10) + CANCEL_GRACE)
+        );
+        assert_eq!(
+            due_action(&pending, None, t0 + Duration::from_secs(10) + CANCEL_GRACE),
+            DueAction::AnswerCancelled
+        );
+
+        // A timeout that passes first still applies
+        pending.cancelled_at = Some(t0 + Duration::from_secs(59));
+        assert_eq!(
+            due_action(&pending, timeout, t0 + Duration::from_secs(60)),
+            DueAction::TimedOut(Duration::from_secs(60))
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::error::ProxyError;
+use crate::framing::LspFrameWriter;
+use crate::message::RpcMessage;
+use crate::venv;
+use crate::watcher::{self, VenvWatcher, WatchEvent};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Quiet period after the last venv layout event before open documents are re-resolved
+const LAYOUT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Quiet period after the last package change in a venv before its backend is restarted
+/// (installs touch `site-packages` for several seconds)
+const PACKAGE_CHANGE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Venv watcher and its pending debounce deadlines
+#[derive(Default)]
+pub(crate) struct VenvWatchState {
+    watcher: Option<VenvWatcher>,
+    /// Pooled venvs whose package directories are currently watched
+    watched_pool: HashSet<PathBuf>,
+    /// Re-resolve open documents once this passes
+    layout_deadline: Option<Instant>,
+    /// Restart each venv's backend once its deadline passes
+    restart_deadlines: HashMap<PathBuf, Instant>,
+    /// Env directories created without a marker yet (`pyvenv.cfg` is written after the
+    /// directory); watched until the marker appears
+    unmarked_envs: HashSet<PathBuf>,
+}
+
+impl VenvWatchState {
+    pub fn is_active(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Nearest pending debounce deadline
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.restart_deadlines
+            .values()
+            .copied()
+            .chain(self.layout_deadline)
+            .min()
+    }
+}
+
+/// A document whose resolved venv changed after a filesystem event
+struct MovedDocument {
+    url: url::Url,
+    old_venv: Option<PathBuf>,
+    new_venv: Option<PathBuf>,
+}
+
+impl super::LspProxy {
+    /// Start the venv watcher if enabled by config.
+    /// Failure to start it is not fatal: venv changes are then only noticed on didOpen.
+    pub(crate) fn start_venv_watcher(&mut self, tx: mpsc::Sender<WatchEvent>) {
+        let config = &self.state.config;
+        let watch_layout = config.watch_venvs && self.state.git_toplevel.is_some();
+        if !watch_layout && !config.restart_on_package_change {
+            return;
+        }
+
+        match VenvWatcher::new(config.venv_dirs.clone(), config.venv_markers.clone(), tx) {
+            Ok(watcher) => self.venv_watch.watcher = Some(watcher),
+            Err(e) => {
+                tracing::warn!(error = ?e, "Failed to start venv watcher, venv changes will only be noticed on didOpen");
+            }
+        }
+    }
+
+    /// Point the venv watcher at the directories of the currently open documents
+    /// and the package directories of pooled venvs
+    pub(crate) fn sync_venv_watcher(&mut self) {
+        let Some(watcher) = self.venv_watch.watcher.as_mut() else {
+            return;
+        };
+        let config = &self.state.config;
+
+        let mut dirs = HashSet::new();
+        let mut env_locations = HashSet::new();
+        if let (true, Some(toplevel)) = (config.watch_venvs, self.state.git_toplevel.as_deref()) {
+            let file_paths: Vec<PathBuf> = self
+                .state
+                .open_documents
+                .keys()
+                .filter_map(|url| url.to_file_path().ok())
+                .collect();
+            dirs = watcher::watch_dirs_for(
+                file_paths.iter().map(|p| p.as_path()),
+                toplevel,
+                &config.venv_dirs,
+            );
+            env_locations =
+                venv::env_locations(file_paths.iter().map(|p| p.as_path()), toplevel, config);
+
+            let unmarked = &mut self.venv_watch.unmarked_envs;
+            unmarked.retain(|dir| dir.is_dir() && !venv::env_exists(dir, &config.venv_markers));
+            dirs.extend(unmarked.iter().cloned());
+        }
+
+        let pooled: HashSet<PathBuf> = self.state.pool.backends_keys().into_iter().collect();
+        if config.restart_on_package_change {
+            for venv_path in &pooled {
+                dirs.extend(watcher::package_watch_dirs(venv_path));
+            }
+        }
+        self.venv_watch.watched_pool = pooled;
+
+        watcher.sync(dirs, env_locations);
+
+        // A marker written before its directory was watched produced no event
+        if self
+            .venv_watch
+            .unmarked_envs
+            .iter()
+            .any(|dir| venv::env_exists(dir, &config.venv_markers))
+        {
+            tracing::debug!("Venv marker appeared before its directory was watched, rescanning");
+            self.venv_watch.layout_deadline = Some(Instant::now() + LAYOUT_DEBOUNCE);
+        }
+    }
+
+    /// Re-sync the watcher if backends were added to or removed from the pool
+    pub(crate) fn sync_venv_watcher_if_pool_changed(&mut self) {
+        if !self.venv_watch.is_active() || !self.state.config.restart_on_package_change {
+            return;
+        }
+        let unchanged = self.venv_watch.watched_pool.len() == self.state.pool.len()
+            && self
+                .venv_watch
+                .watched_pool
+                .iter()
+                .all(|venv_path| self.state.pool.contains(venv_path));
+        if !unchanged {
+            self.sync_venv_watcher();
+        }
+    }
+
+    /// Classify a watch event and (re)start the matching debounce window
+    pub(crate) fn handle_watch_event(&mut self, event: WatchEvent) {
+        tracing::debug!(paths = ?event.paths, "Venv watch event");
+        let config = &self.state.config;
+        let now = Instant::now();
+
+        for path in &event.paths {
+            let is_env_dir = config.venv_dirs.iter().any(|d| path.ends_with(d))
+                || self
+                    .venv_watch
+                    .watcher
+                    .as_ref()
+                    .is_some_and(|w| w.is_env_path(path));
+            if config.watch_venvs
+                && (is_env_dir
+                    || watcher::is_layout_path(path, &config.venv_dirs, &config.venv_markers))
+            {
+                self.venv_watch.layout_deadline = Some(now + LAYOUT_DEBOUNCE);
+                if is_env_dir && path.is_dir() && !venv::env_exists(path, &config.venv_markers) {
+                    self.venv_watch.unmarked_envs.insert(path.clone());
+                }
+            }
+            if config.restart_on_package_change {
+                if let Some(venv_path) =
+                    watcher::package_change_venv(path, &self.venv_watch.watched_pool)
+                {
+                    self.venv_watch
+                        .restart_deadlines
+                        .insert(venv_path.clone(), now + PACKAGE_CHANGE_DEBOUNCE);
+                }
+            }
+        }
+    }
+
+    /// Act on debounce windows that have elapsed
+    pub(crate) async fn handle_watch_deadlines(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let now = Instant::now();
+
+        if self.venv_watch.layout_deadline.is_some_and(|d| d <= now) {
+            self.venv_watch.layout_deadline = None;
+            self.handle_venv_changes(client_writer).await?;
+        }
+
+        let due: Vec<PathBuf> = self
+            .venv_watch
+            .restart_deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(venv_path, _)| venv_path.clone())
+            .collect();
+        for venv_path in due {
+            self.venv_watch.restart_deadlines.remove(&venv_path);
+            // Removed venvs are handled by the layout rescan
+            if self.state.pool.contains(&venv_path)
+                && venv::env_exists(&venv_path, &self.state.config.venv_markers)
+            {
+                tracing::info!(venv = %venv_path.display(), "Installed packages changed, restarting backend");
+                self.restart_backend(&venv_path, client_writer).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-resolve the venv of every open document after venvs were created or removed.
+    ///
+    /// 1. Backends whose env no longer exists are torn down
+    /// 2. Moved documents are closed on their old backend and their diagnostics cleared
+    /// 3. Moved documents are opened on their new backend (spawned if needed)
+    pub(crate) async fn handle_venv_changes(
+        &mut self,
+        client_writer: &mut LspFrameWriter<tokio::io::Stdout>,
+    ) -> Result<(), ProxyError> {
+        let moved = self.resolve_moved_documents().await;
+
+        // Tear down backends whose env disappeared (before doc.venv is updated,
+        // so their documents' diagnostics are cleared)
+        let removed: Vec<PathBuf> = self
+            .state
+            .pool
+            .backends_keys()
+            .into_iter()
+            .filter(|venv_path| !venv::env_exists(venv_path, &self.state.config.venv_markers))
+            .collect();
+        for venv_path in &removed {
+            tracing::info!(venv = %venv_path.display(), "Venv removed, shutting down backend");
+            self.teardown_backend(venv_path, client_writer).await?;
+        }
+        for venv_path in self.boot.venvs() {
+            if venv::env_exists(&venv_path, &self.state.config.venv_markers)
+                || self.state.pool.contains(&venv_path)
+            {
+                continue;
+            }
+            if let Some(boot) = self.boot.abort(&venv_path) {
+                tracing::info!(venv = %venv_path.display(), "Venv removed, aborting backend boot");
+                let e = ProxyError::Venv(crate::error::VenvError::Removed(venv_path.clone()));
+                self.fail_queued_messages(boot.queue, &e, client_writer)
+                    .await?;
+            }
+        }
+
+        if moved.is_empty() {
+            self.sync_venv_watcher();
+            return Ok(());
+        }
+
+        tracing::info!(moved_docs = moved.len(), "Migrating documents to new venvs");
+
+        // Close moved documents on their old backend
+        for doc in &moved {
+            let Some(old_venv) = &doc.old_venv else {
+                continue;
+            };
+            if let Some(inst) = self.state.pool.get_mut(old_venv) {
+                let did_close = RpcMessage {
+                    jsonrpc: "2.0".to_string(),
+                    id: None,
+                    method: Some("textDocument/didClose".to_string()),
+                    params: Some(serde_json::json!({
+                        "textDocument": { "uri": doc.url.to_string() }
+                    })),
+                    result: None,
+                    error: None,
+                };
+                if let Err(e) = inst.send(&did_close).await {
+                    tracing::warn!(venv = %old_venv.display(), error = ?e, "Failed to send didClose to old backend");
+                }
+            }
+        }
+
+        // Diagnostics from the old backend are stale
+        let urls: Vec<url::Url> = moved.iter().map(|doc| doc.url.clone()).collect();
+        self.clear_diagnostics_for_uris(&urls, client_writer).await;
+
+        // Update cache and group by new venv
+        let mut by_new_venv: HashMap<PathBuf, Vec<url::Url>> = HashMap::new();
+        for doc in moved {
+            tracing::info!(
+                uri = %doc.url,
+                old_venv = ?doc.old_venv.as_ref().map(|p| p.display().to_string()),
+                new_venv = ?doc.new_venv.as_ref().map(|p| p.display().to_string()),
+                "Document venv changed"
+            );
+            if let Some(open_doc) = self.state.open_documents.get_mut(&doc.url) {
+                open_doc.venv = doc.new_venv.clone();
+            }
+            if let Some(new_venv) = doc.new_venv {
+                by_new_venv.entry(new_venv).or_default().push(doc.url);
+            }
+        }
+
+        // Open moved documents on their new backend
+        for (venv_path, urls) in by_new_venv {
+            if self.state.pool.contains(&venv_path) {
+                self.replay_did_open(&venv_path, &urls).await;
+                continue;
+            }
+
+            // A booted backend restores every document of this venv
+            let project_dir = urls
+                .first()
+                .and_then(|url| url.to_file_path().ok())
+                .and_then(|path| path.parent().map(Path::to_path_buf));
+            let kind = self.backend_kind_for(&venv_path, project_dir.as_deref());
+            match self
+                .start_backend_boot(&venv_path, kind, false, client_writer)
+                .await
+            {
+                Ok(()) => {}
+                Err(e) => {
+                    tracing::error!(
+                        venv = %venv_path.display(),
+                        error = ?e,
+                        "Failed to create backend for migrated documents"
+                    );
+                    self.notify_backend_error(&venv_path, &e, client_writer)
+                        .await;
+                }
+            }
+        }
+
+        self.sync_venv_watcher();
+        Ok(())
+    }
+
+    /// Re-run venv resolution for all open documents and collect those whose venv changed
+    async fn resolve_moved_documents(&self) -> Vec<MovedDocument> {
+        let mut moved = Vec::new();
+        for (url, doc) in &self.state.open_documents {
+            let Ok(file_path) = url.to_file_path() else {
+                continue;
+            };
+            let new_venv = match venv::find_venv(
+                &file_path,
+                self.state.git_toplevel.as_deref(),
+                &self.state.config,
+            )
+            .await
+            {
+                Ok(found) => found,
+                Err(e) => {
+                    // Already reported to the user on didOpen; the document has no usable env
+                    tracing::warn!(uri = %url, error = ?e, "venv resolution failed after filesystem change");
+                    None
+                }
+            };
+            if new_venv != doc.venv {
+                moved.push(MovedDocument {
+                    url: url.clone(),
+                    old_venv: doc.venv.clone(),
+                    new_venv,
+                });
+            }
+        }
+        moved
+    }
+
+    /// Send didOpen for cached documents to an already running backend
+    async fn replay_did_open(&mut self, venv_path: &Path, urls: &[url::Url]) {
+        let Some(inst) = self.state.pool.get_mut(venv_path) else {
+            return;
+        };
+        inst.last_used = Instant::now();
+
+        for url in urls {
+            let Some(doc) = self.state.open_documents.get(url) else {
+                continue;
+            };
+            let did_open = RpcMessage {
+                jsonrpc: "2.0".to_string(),
+                id: None,
+                method: Some("textDocument/didOpen".to_string()),
+                params: Some(serde_json::json!({
+                    "textDocument": {
+                        "uri": url.to_string(),
+                        "languageId": doc.language_id,
+                        "version": doc.version,
+                        "text": doc.text,
+                    }
+                })),
+                result: None,
+                error: None,
+            };
+            match inst.send(&did_open).await {
+                Ok(()) => {
+                    tracing::info!(venv = %venv_path.display(), uri = %url, "Replayed didOpen to backend");
+                }
+                Err(e) => {
+                    tracing::warn!(venv = %venv_path.display(), uri = %url, error = ?e, "Failed to replay didOpen");
+                }
+            }
+        }
+    }
+}
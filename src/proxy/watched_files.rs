@@ -0,0 +1,179 @@
+use crate::error::ProxyError;
+use crate::message::RpcMessage;
+use crate::venv;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::time::Instant;
+
+/// Where a watched-file change is forwarded
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChangeTarget {
+    /// The backend of this venv only
+    Venv(PathBuf),
+    /// Every backend: shared paths, files outside any venv, non-file URIs
+    All,
+}
+
+/// The changes each backend receives, in their original order.
+/// Backends without any relevant change are left out.
+fn split_changes(
+    changes: &[(Value, ChangeTarget)],
+    backends: &[PathBuf],
+) -> HashMap<PathBuf, Vec<Value>> {
+    backends
+        .iter()
+        .filter_map(|backend| {
+            let subset: Vec<Value> = changes
+                .iter()
+                .filter(|(_, target)| match target {
+                    ChangeTarget::Venv(venv) => venv == backend,
+                    ChangeTarget::All => true,
+                })
+                .map(|(change, _)| change.clone())
+                .collect();
+            (!subset.is_empty()).then(|| (backend.clone(), subset))
+        })
+        .collect()
+}
+
+impl super::LspProxy {
+    /// Forward `workspace/didChangeWatchedFiles` to the backends the changed files
+    /// belong to, so a save in one project doesn't make every backend re-check.
+    /// Changes under `shared_paths` (or outside any venv) still go to every backend.
+    pub(crate) async fn dispatch_did_change_watched_files(
+        &mut self,
+        msg: &RpcMessage,
+    ) -> Result<(), ProxyError> {
+        let Some(changes) = msg
+            .params
+            .as_ref()
+            .and_then(|p| p.get("changes"))
+            .and_then(|c| c.as_array())
+        else {
+            return self.dispatch_client_notification(msg).await;
+        };
+
+        // A checkout can touch thousands of files: resolve each directory once
+        let mut dir_venvs = HashMap::new();
+        let mut targeted = Vec::with_capacity(changes.len());
+        for change in changes {
+            let target = self.watched_file_target(change, &mut dir_venvs).await;
+            targeted.push((change.clone(), target));
+        }
+
+        let mut backends = self.state.pool.backends_keys();
+        backends.extend(self.boot.venvs());
+        let subsets = split_changes(&targeted, &backends);
+        tracing::debug!(
+            changes = targeted.len(),
+            backends = subsets.len(),
+            "Routing watched-file changes"
+        );
+
+        for (venv_path, subset) in subsets {
+            let mut forwarded = msg.clone();
+            if let Some(params) = forwarded.params.as_mut() {
+                params["changes"] = Value::Array(subset);
+            }
+            if let Some(boot) = self.boot.get_mut(&venv_path) {
+                boot.queue.push(forwarded.clone());
+            }
+            if let Some(inst) = self.state.pool.get_mut(&venv_path) {
+                inst.last_used = Instant::now();
+                if let Err(e) = inst.send(&forwarded).await {
+                    tracing::warn!(venv = %venv_path.display(), error = ?e, "Failed to forward watched-file changes");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve a `FileEvent`'s venv: shared paths first, then the open-document cache,
+    /// then a venv search (memoized per directory in `dir_venvs`)
+    async fn watched_file_target(
+        &self,
+        change: &Value,
+        dir_venvs: &mut HashMap<PathBuf, Option<PathBuf>>,
+    ) -> ChangeTarget {
+        let Some(url) = change
+            .get("uri")
+            .and_then(|uri| uri.as_str())
+            .and_then(|uri| url::Url::parse(uri).ok())
+        else {
+            return ChangeTarget::All;
+        };
+        let Ok(file_path) = url.to_file_path() else {
+            return ChangeTarget::All;
+        };
+        if self.state.config.is_shared_path(&file_path) {
+            return ChangeTarget::All;
+        }
+        if let Some(venv) = self.venv_for_uri(&url) {
+            return ChangeTarget::Venv(venv);
+        }
+
+        let dir = file_path.parent().unwrap_or(&file_path).to_path_buf();
+        let venv = match dir_venvs.get(&dir) {
+            Some(venv) => venv.clone(),
+            None => {
+                let venv = venv::find_venv(
+                    &file_path,
+                    self.state.git_toplevel.as_deref(),
+                    &self.state.config,
+                )
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::debug!(uri = %url, error = ?e, "Failed to resolve venv for watched file");
+                    None
+                });
+                dir_venvs.insert(dir, venv.clone());
+                venv
+            }
+        };
+        venv.map_or(ChangeTarget::All, ChangeTarget::Venv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn split_changes_sends_each_backend_its_files_and_shared_ones() {
+        let a = PathBuf::from("/r/a/.venv");
+        let b = PathBuf::from("/r/b/.venv");
+        let c = PathBuf::from("/r/c/.venv");
+        let change = |path: &str| json!({"uri": format!("file://{}", path), "type": 2});
+        let changes = vec![
+            (change("/r/a/app.py"), ChangeTarget::Venv(a.clone())),
+            (change("/r/libs/util.py"), ChangeTarget::All),
+            (change("/r/b/main.py"), ChangeTarget::Venv(b.clone())),
+            (change("/r/a/models.py"), ChangeTarget::Venv(a.clone())),
+            (
+                change("/r/d/other.py"),
+                ChangeTarget::Venv("/r/d/.venv".into()),
+            ),
+        ];
+
+        let subsets = split_changes(&changes, &[a.clone(), b.clone()]);
+        assert_eq!(subsets.len(), 2);
+        assert_eq!(
+            subsets[&a],
+            vec![
+                change("/r/a/app.py"),
+                change("/r/libs/util.py"),
+                change("/r/a/models.py")
+            ]
+        );
+        assert_eq!(
+            subsets[&b],
+            vec![change("/r/libs/util.py"), change("/r/b/main.py")]
+        );
+
+        // Nothing relevant: the backend gets no notification at all
+        let subsets = split_changes(&changes[..1], &[b, c]);
+        assert!(subsets.is_empty());
+    }
+}
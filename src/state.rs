@@ -1,18 +1,48 @@
 use crate::backend::BackendKind;
 use crate::backend_pool::BackendPool;
+use crate::config::Config;
 use crate::message::{RpcId, RpcMessage};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
+use tokio::time::Instant;
 use url::Url;
 
-/// Information about pending requests
+/// Information about pending requests (client → backend), keyed by the proxy-assigned
+/// id the request was sent to the backend with
 #[derive(Debug, Clone)]
 pub struct PendingRequest {
+    /// Original client-assigned ID (to restore when forwarding the response to the client)
+    pub client_id: RpcId,
     /// Backend session this request was sent to
     pub backend_session: u64,
     /// Venv path of the backend this request was sent to
     pub venv_path: PathBuf,
+    /// The request with the proxy-assigned ID (replayed if the backend is restarted while
+    /// it is in flight)
+    pub request: RpcMessage,
+    /// When the request was last written to a backend (for hang detection)
+    pub sent_at: Instant,
+    /// When the client cancelled it (answered by the proxy if the backend stays silent)
+    pub cancelled_at: Option<Instant>,
+}
+
+impl PendingRequest {
+    /// The backend's response to this request, carrying the client's original ID
+    pub fn client_response(&self, mut response: RpcMessage) -> RpcMessage {
+        response.id = Some(self.client_id.clone());
+        response
+    }
+}
+
+/// A request the proxy already answered (timeout, or a cancel the backend didn't
+/// answer) while the backend still owes a response, which is dropped when it arrives
+#[derive(Debug, Clone)]
+pub struct LateRequest {
+    pub venv_path: PathBuf,
+    pub backend_session: u64,
+    /// When the proxy answered it (and sent the backend its cancel)
+    pub answered_at: Instant,
 }
 
 /// Information about a pending server→client request (backend → proxy → client)
@@ -38,21 +68,39 @@ pub struct OpenDocument {
 
 /// State held by proxy
 pub struct ProxyState {
-    /// Which LSP backend to use
+    /// Default LSP backend (overridable per venv, see `venv::resolve_backend_kind`)
     pub backend_kind: BackendKind,
 
+    /// Backend kind of each venv, resolved once so the documents of a venv cannot
+    /// switch it back and forth (forgotten when the venv's backend is torn down)
+    pub venv_kinds: HashMap<PathBuf, BackendKind>,
+
     /// Git toplevel (search boundary, cached on first retrieval)
     pub git_toplevel: Option<PathBuf>,
 
+    /// Project config (`.typemux.toml` at the git toplevel, loaded at startup)
+    pub config: Config,
+
     /// Initialize message from Claude Code (reused for backend initialization)
     pub client_initialize: Option<RpcMessage>,
 
     /// Open documents
     pub open_documents: HashMap<Url, OpenDocument>,
 
-    /// Pending requests (client → backend)
+    /// Pending requests (client → backend, keyed by backend request ID)
+    /// Maps the ID sent to the backend → PendingRequest holding the client's original ID
+    /// Mutate it through `register_request` / `remove_pending`, which keep `client_requests`
+    /// in sync.
     pub pending_requests: HashMap<RpcId, PendingRequest>,
 
+    /// Client request ID → backend request ID of the pending request it names. A client
+    /// reusing the ID of a still pending request names the latest one from then on.
+    client_requests: HashMap<RpcId, RpcId>,
+
+    /// Requests already answered by the proxy whose backend response is still due
+    /// (keyed by backend request ID). Not pending: they don't keep a backend busy.
+    pub late_requests: HashMap<RpcId, LateRequest>,
+
     /// Pending backend requests (backend → client, keyed by proxy_id)
     /// Maps proxy_id → PendingBackendRequest to route client responses back to correct backend
     pub pending_backend_requests: HashMap<RpcId, PendingBackendRequest>,
@@ -60,6 +108,9 @@ pub struct ProxyState {
     /// Next proxy ID for server→client requests (monotonically increasing to avoid collisions)
     pub next_proxy_request_id: i64,
 
+    /// Next proxy ID for requests sent to backends (client requests, fan-out, probes)
+    pub next_backend_request_id: i64,
+
     /// Backend pool
     pub pool: BackendPool,
 }
@@ -72,12 +123,17 @@ impl ProxyState {
     ) -> Self {
         Self {
             backend_kind,
+            venv_kinds: HashMap::new(),
             git_toplevel: None,
+            config: Config::default(),
             client_initialize: None,
             open_documents: HashMap::new(),
             pending_requests: HashMap::new(),
+            client_requests: HashMap::new(),
+            late_requests: HashMap::new(),
             pending_backend_requests: HashMap::new(),
             next_proxy_request_id: -1, // Use negative IDs to avoid collision with client IDs
+            next_backend_request_id: 1,
             pool: BackendPool::new(max_backends, backend_ttl),
         }
     }
@@ -89,4 +145,297 @@ impl ProxyState {
         self.next_proxy_request_id -= 1;
         RpcId::Number(id)
     }
+
+    /// Allocate a new request ID for a request sent to a backend.
+    /// Unique across backends and never reused, so a response can only match the
+    /// request it answers, however clients number (or reuse) their own IDs.
+    pub fn alloc_backend_request_id(&mut self) -> RpcId {
+        let id = self.next_backend_request_id;
+        self.next_backend_request_id += 1;
+        RpcId::Number(id)
+    }
+
+    /// Register a client request in `pending_requests` under a new backend request ID.
+    /// Returns the request to send (or queue) for the backend, carrying that ID.
+    pub fn register_request(
+        &mut self,
+        msg: &RpcMessage,
+        venv_path: &Path,
+        session: u64,
+    ) -> RpcMessage {
+        let Some(client_id) = msg.id.clone() else {
+            return msg.clone();
+        };
+        let backend_id = self.alloc_backend_request_id();
+        let mut forwarded = msg.clone();
+        forwarded.id = Some(backend_id.clone());
+        if let Some(previous) = self
+            .client_requests
+            .insert(client_id.clone(), backend_id.clone())
+        {
+            if self.pending_requests.contains_key(&previous) {
+                tracing::warn!(
+                    client_id = ?client_id,
+                    "Client reused the ID of a pending request; the new request takes it over"
+                );
+            }
+        }
+        self.pending_requests.insert(
+            backend_id,
+            PendingRequest {
+                client_id,
+                backend_session: session,
+                venv_path: venv_path.to_path_buf(),
+                request: forwarded.clone(),
+                sent_at: Instant::now(),
+                cancelled_at: None,
+            },
+        );
+        forwarded
+    }
+
+    /// Hand the in-flight requests of a restarted backend's old session over to its
+    /// replacement `new_session`. Returns the requests to replay (in the order they
+    /// were first sent) and the ones the client cancelled meanwhile, which are removed
+    /// so they can be answered instead. Late responses the old session owed are forgotten.
+    pub fn take_restart_replay(
+        &mut self,
+        venv_path: &Path,
+        old_session: u64,
+        new_session: u64,
+    ) -> (Vec<RpcMessage>, Vec<PendingRequest>) {
+        self.forget_late_requests(venv_path, old_session);
+        let of_old_session =
+            |p: &PendingRequest| p.venv_path == venv_path && p.backend_session == old_session;
+
+        let cancelled_ids: Vec<RpcId> = self
+            .pending_requests
+            .iter()
+            .filter(|(_, p)| of_old_session(p) && p.cancelled_at.is_some())
+            .map(|(id, _)| id.clone())
+            .collect();
+        let cancelled = cancelled_ids
+            .iter()
+            .filter_map(|id| self.remove_pending(id))
+            .collect();
+
+        let mut replay: Vec<(&RpcId, &mut PendingRequest)> = self
+            .pending_requests
+            .iter_mut()
+            .filter(|(_, p)| of_old_session(p))
+            .collect();
+        // Backend request ids increase with every request sent
+        replay.sort_by_key(|(id, _)| match id {
+            RpcId::Number(n) => *n,
+            RpcId::String(_) => i64::MAX,
+        });
+        let replay = replay
+            .into_iter()
+            .map(|(_, p)| {
+                p.backend_session = new_session;
+                p.request.clone()
+            })
+            .collect();
+        (replay, cancelled)
+    }
+
+    /// Backend request ID of the unanswered request the client sent with `client_id`
+    /// (the latest one, if the client reused the ID)
+    pub fn pending_request_id(&self, client_id: &RpcId) -> Option<RpcId> {
+        self.client_requests
+            .get(client_id)
+            .filter(|id| self.pending_requests.contains_key(*id))
+            .cloned()
+    }
+
+    /// Remove a pending request (answered, cancelled or failed) by its backend request ID
+    pub fn remove_pending(&mut self, id: &RpcId) -> Option<PendingRequest> {
+        let pending = self.pending_requests.remove(id)?;
+        if self.client_requests.get(&pending.client_id) == Some(id) {
+            self.client_requests.remove(&pending.client_id);
+        }
+        Some(pending)
+    }
+
+    /// Move a request the proxy just answered from pending to late
+    pub fn mark_late(&mut self, id: &RpcId, now: Instant) {
+        if let Some(pending) = self.remove_pending(id) {
+            self.late_requests.insert(
+                id.clone(),
+                LateRequest {
+                    venv_path: pending.venv_path,
+                    backend_session: pending.backend_session,
+                    answered_at: now,
+                },
+            );
+        }
+    }
+
+    /// Forget the late responses a backend session owes (the session ended)
+    pub fn forget_late_requests(&mut self, venv_path: &Path, session: u64) {
+        self.late_requests
+            .retain(|_, late| !(late.venv_path == venv_path && late.backend_session == session));
+    }
+
+    /// Forget late responses that did not arrive within `grace` of the proxy's answer.
+    /// The backend was sent a cancel, so it need not answer at all; a response arriving
+    /// afterwards is dropped as unknown.
+    pub fn expire_late_requests(&mut self, now: Instant, grace: Duration) {
+        self.late_requests
+            .retain(|_, late| now.saturating_duration_since(late.answered_at) < grace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pending(state: &mut ProxyState, client_id: i64, venv: &str, session: u64) -> RpcId {
+        let request = RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(RpcId::Number(client_id)),
+            method: Some("textDocument/hover".to_string()),
+            params: Some(json!({})),
+            result: None,
+            error: None,
+        };
+        let sent = state.register_request(&request, Path::new(venv), session);
+        sent.id.unwrap()
+    }
+
+    #[test]
+    fn restart_replays_old_session_requests_in_order() {
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        let first = pending(&mut state, 10, "/a/.venv", 1);
+        let cancelled = pending(&mut state, 11, "/a/.venv", 1);
+        let timed_out = pending(&mut state, 12, "/a/.venv", 1);
+        let other_venv = pending(&mut state, 13, "/b/.venv", 1);
+        let last = pending(&mut state, 14, "/a/.venv", 1);
+        state
+            .pending_requests
+            .get_mut(&cancelled)
+            .unwrap()
+            .cancelled_at = Some(Instant::now());
+        state.mark_late(&timed_out, Instant::now());
+
+        let (replay, answered) = state.take_restart_replay(Path::new("/a/.venv"), 1, 2);
+
+        let replayed: Vec<Option<RpcId>> = replay.into_iter().map(|m| m.id).collect();
+        assert_eq!(replayed, vec![Some(first.clone()), Some(last.clone())]);
+        assert_eq!(answered.len(), 1);
+        assert_eq!(answered[0].client_id, RpcId::Number(11));
+
+        // Replayed requests now belong to the new session; the rest are gone or untouched
+        assert_eq!(state.pending_requests[&first].backend_session, 2);
+        assert_eq!(state.pending_requests[&last].backend_session, 2);
+        assert_eq!(state.pending_requests[&other_venv].backend_session, 1);
+        assert!(!state.pending_requests.contains_key(&cancelled));
+        assert!(!state.pending_requests.contains_key(&timed_out));
+        assert!(state.late_requests.is_empty());
+    }
+
+    #[test]
+    fn request_ids_are_rewritten_and_restored() {
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        let request = |id: RpcId| RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(id),
+            method: Some("textDocument/hover".to_string()),
+            params: Some(json!({})),
+            result: None,
+            error: None,
+        };
+        // The client reuses an ID across backends
+        let client_id = RpcId::String("req-1".to_string());
+        let to_a = state.register_request(&request(client_id.clone()), Path::new("/a/.venv"), 1);
+        let to_b = state.register_request(&request(client_id.clone()), Path::new("/b/.venv"), 2);
+        let (a_id, b_id) = (to_a.id.clone().unwrap(), to_b.id.clone().unwrap());
+        assert_ne!(a_id, b_id);
+        assert_ne!(a_id, client_id);
+        assert_eq!(
+            state.pending_requests[&b_id].venv_path,
+            Path::new("/b/.venv")
+        );
+        assert_eq!(state.pending_requests[&b_id].request.id, Some(b_id.clone()));
+
+        // The backend answers with its ID; the client gets its own back
+        let response = RpcMessage {
+            jsonrpc: "2.0".to_string(),
+            id: Some(a_id.clone()),
+            method: None,
+            params: None,
+            result: Some(json!({"contents": "a"})),
+            error: None,
+        };
+        let pending = state.remove_pending(&a_id).unwrap();
+        assert_eq!(pending.backend_session, 1);
+        let forwarded = pending.client_response(response);
+        assert_eq!(forwarded.id, Some(client_id.clone()));
+        assert_eq!(forwarded.result, Some(json!({"contents": "a"})));
+        assert_eq!(state.pending_request_id(&client_id), Some(b_id));
+
+        // Notifications carry no ID and are not registered
+        let mut notification = request(client_id);
+        notification.id = None;
+        let sent = state.register_request(&notification, Path::new("/a/.venv"), 1);
+        assert_eq!(sent.id, None);
+        assert_eq!(state.pending_requests.len(), 1);
+    }
+
+    #[test]
+    fn reused_client_id_names_the_latest_pending_request() {
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        let first = pending(&mut state, 10, "/a/.venv", 1);
+        let second = pending(&mut state, 10, "/b/.venv", 1);
+        let other = pending(&mut state, 11, "/a/.venv", 1);
+        assert_eq!(
+            state.pending_request_id(&RpcId::Number(10)),
+            Some(second.clone())
+        );
+        assert_eq!(state.pending_request_id(&RpcId::Number(11)), Some(other));
+
+        // Answering the earlier request leaves the latest one in place
+        state.remove_pending(&first);
+        assert_eq!(
+            state.pending_request_id(&RpcId::Number(10)),
+            Some(second.clone())
+        );
+
+        // Once it is answered too, the ID names nothing
+        state.mark_late(&second, Instant::now());
+        assert_eq!(state.pending_request_id(&RpcId::Number(10)), None);
+
+        // The earlier request is unreachable by ID while the latest is pending
+        let first = pending(&mut state, 12, "/a/.venv", 1);
+        let second = pending(&mut state, 12, "/b/.venv", 1);
+        state.remove_pending(&second);
+        assert_eq!(state.pending_request_id(&RpcId::Number(12)), None);
+        assert!(state.pending_requests.contains_key(&first));
+    }
+
+    #[test]
+    fn late_requests_leave_pending_and_expire() {
+        let mut state = ProxyState::new(BackendKind::Pyright, 4, None);
+        let answered = pending(&mut state, 10, "/a/.venv", 1);
+        let other_session = pending(&mut state, 11, "/a/.venv", 2);
+        let t0 = Instant::now();
+        state.mark_late(&answered, t0);
+        state.mark_late(&other_session, t0);
+
+        // No longer pending for the client or the backend's pending counts
+        assert!(state.pending_requests.is_empty());
+        assert_eq!(state.pending_request_id(&RpcId::Number(10)), None);
+        assert_eq!(state.late_requests[&answered].backend_session, 1);
+
+        // Expired after the grace period
+        let later = t0 + Duration::from_secs(3);
+        state.expire_late_requests(t0 + Duration::from_secs(1), Duration::from_secs(2));
+        assert_eq!(state.late_requests.len(), 2);
+
+        state.forget_late_requests(Path::new("/a/.venv"), 1);
+        assert!(!state.late_requests.contains_key(&answered));
+        state.expire_late_requests(later, Duration::from_secs(2));
+        assert!(state.late_requests.is_empty());
+    }
 }
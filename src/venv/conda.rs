@@ -0,0 +1,272 @@
+//! Conda / mamba environment resolution.
+//!
+//! Conda envs have no `pyvenv.cfg`; a project declares its env in
+//! `environment.yml` (`name:` or `prefix:`) and the env lives under one of
+//! conda's `envs_dirs`. A directory is accepted as a conda env only if it
+//! contains `conda-meta/history`.
+
+use std::path::{Component, Path, PathBuf};
+
+const ENVIRONMENT_FILES: &[&str] = &["environment.yml", "environment.yaml"];
+const CONDA_META_HISTORY: &str = "conda-meta/history";
+
+/// Well-known installation roots (relative to `$HOME`) whose `envs/` are searched last
+const DEFAULT_INSTALL_ROOTS: &[&str] = &[
+    "miniforge3",
+    "mambaforge",
+    "miniconda3",
+    "anaconda3",
+    "micromamba",
+    ".conda",
+];
+
+/// Check whether `path` is a conda environment (has `conda-meta/history`)
+pub fn is_conda_env(path: &Path) -> bool {
+    path.join(CONDA_META_HISTORY).exists()
+}
+
+/// Resolve the conda env declared by `environment.yml` in a project directory.
+///
+/// Returns None if there is no environment file or the env has not been created yet.
+pub(super) fn find_conda_env(project_dir: &Path) -> Option<PathBuf> {
+    let candidates = env_candidates(project_dir);
+    let found = candidates.iter().find(|env| is_conda_env(env)).cloned();
+    if found.is_none() && !candidates.is_empty() {
+        tracing::debug!(
+            project = %project_dir.display(),
+            candidates = ?candidates,
+            "Declared conda env does not exist (missing conda-meta/history)"
+        );
+    }
+    found
+}
+
+/// Where the env declared by `environment.yml` in `project_dir` appears once created
+pub(super) fn env_watch_locations(project_dir: &Path) -> Vec<super::EnvLocation> {
+    env_candidates(project_dir)
+        .into_iter()
+        .map(super::EnvLocation::Env)
+        .collect()
+}
+
+/// Paths the declared env may live at, in priority order: its `prefix`, then
+/// `<envs_dir>/<name>` for each of conda's `envs_dirs`
+fn env_candidates(project_dir: &Path) -> Vec<PathBuf> {
+    let Some(env_file) = ENVIRONMENT_FILES
+        .iter()
+        .map(|f| project_dir.join(f))
+        .find(|p| p.exists())
+    else {
+        return Vec::new();
+    };
+
+    let content = match std::fs::read_to_string(&env_file) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::warn!(path = %env_file.display(), error = ?e, "Failed to read conda environment file");
+            return Vec::new();
+        }
+    };
+    let spec = parse_environment_file(&content);
+
+    let mut candidates = Vec::new();
+    if let Some(prefix) = spec.prefix {
+        let prefix: PathBuf = super::expand_home(&prefix)
+            .components()
+            .filter(|c| !matches!(c, Component::CurDir))
+            .collect();
+        candidates.push(if prefix.is_absolute() {
+            prefix
+        } else {
+            project_dir.join(prefix)
+        });
+    }
+    if let Some(name) = spec.name {
+        let envs_dirs = envs_dirs();
+        tracing::debug!(
+            project = %project_dir.display(),
+            name = %name,
+            envs_dirs = ?envs_dirs,
+            "Searching for conda env"
+        );
+        candidates.extend(envs_dirs.into_iter().map(|dir| dir.join(&name)));
+    }
+    candidates
+}
+
+/// Top-level `name:` / `prefix:` values of an `environment.yml`
+#[derive(Debug, Default, PartialEq)]
+struct EnvironmentSpec {
+    name: Option<String>,
+    prefix: Option<String>,
+}
+
+/// Minimal parser for the two top-level scalar keys we need.
+/// Nested keys (indented lines) and list items are ignored.
+fn parse_environment_file(content: &str) -> EnvironmentSpec {
+    let mut spec = EnvironmentSpec::default();
+    for line in content.lines() {
+        if line.starts_with(char::is_whitespace) || line.starts_with('-') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = yaml_scalar(value);
+        if value.is_empty() {
+            continue;
+        }
+        match key.trim() {
+            "name" => spec.name = Some(value),
+            "prefix" => spec.prefix = Some(value),
+            _ => {}
+        }
+    }
+    spec
+}
+
+/// Strip trailing comments and surrounding quotes from a YAML scalar
+fn yaml_scalar(value: &str) -> String {
+    let value = value.trim();
+    if let Some(quoted) = value
+        .strip_prefix('"')
+        .and_then(|v| v.split_once('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.split_once('\'')))
+    {
+        return quoted.0.to_string();
+    }
+    value
+        .split(" #")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Ordered list of directories that may contain named envs:
+/// 1. `CONDA_ENVS_PATH` / `CONDA_ENVS_DIRS`
+/// 2. `envs_dirs` from `~/.condarc`
+/// 3. Derived from `CONDA_PREFIX` (base install or sibling of the active env)
+/// 4. `MAMBA_ROOT_PREFIX/envs`
+/// 5. Well-known install locations under `$HOME`
+fn envs_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+
+    for var in ["CONDA_ENVS_PATH", "CONDA_ENVS_DIRS"] {
+        if let Some(value) = std::env::var_os(var) {
+            dirs.extend(std::env::split_paths(&value));
+        }
+    }
+
+    if let Some(condarc) = super::home_dir().map(|h| h.join(".condarc")) {
+        if let Ok(content) = std::fs::read_to_string(condarc) {
+            dirs.extend(
+                parse_condarc_envs_dirs(&content)
+                    .iter()
+                    .map(|d| super::expand_home(d)),
+            );
+        }
+    }
+
+    if let Some(prefix) = std::env::var_os("CONDA_PREFIX").map(PathBuf::from) {
+        if prefix.parent().and_then(|p| p.file_name()) == Some("envs".as_ref()) {
+            // Active env is a named env: its siblings live in the same envs dir
+            dirs.extend(prefix.parent().map(Path::to_path_buf));
+        } else {
+            // Active env is the base install
+            dirs.push(prefix.join("envs"));
+        }
+    }
+
+    if let Some(root) = std::env::var_os("MAMBA_ROOT_PREFIX") {
+        dirs.push(PathBuf::from(root).join("envs"));
+    }
+
+    if let Some(home) = super::home_dir() {
+        dirs.extend(
+            DEFAULT_INSTALL_ROOTS
+                .iter()
+                .map(|root| home.join(root).join("envs")),
+        );
+    }
+
+    let mut unique = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// Extract the `envs_dirs:` list from a `.condarc`
+fn parse_condarc_envs_dirs(content: &str) -> Vec<String> {
+    let mut dirs = Vec::new();
+    let mut in_envs_dirs = false;
+    for line in content.lines() {
+        if !line.starts_with(char::is_whitespace) && !line.starts_with('-') {
+            in_envs_dirs = line.trim_end().strip_suffix(':') == Some("envs_dirs");
+            continue;
+        }
+        if in_envs_dirs {
+            if let Some(item) = line.trim().strip_prefix('-') {
+                let item = yaml_scalar(item);
+                if !item.is_empty() {
+                    dirs.push(item);
+                }
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse_environment_file_reads_top_level_keys() {
+        let content = "name: \"data-science\" # comment\nchannels:\n  - conda-forge\ndependencies:\n  - python=3.11\n  - name: nested\n";
+        let spec = parse_environment_file(content);
+        assert_eq!(spec.name.as_deref(), Some("data-science"));
+        assert_eq!(spec.prefix, None);
+    }
+
+    #[test]
+    fn parse_condarc_envs_dirs_list() {
+        let content = "channels:\n  - defaults\nenvs_dirs:\n  - ~/envs\n  - /opt/conda/envs\nauto_activate_base: false\n";
+        assert_eq!(
+            parse_condarc_envs_dirs(content),
+            vec!["~/envs".to_string(), "/opt/conda/envs".to_string()]
+        );
+    }
+
+    #[test]
+    fn find_conda_env_by_prefix() {
+        let temp = tempdir().unwrap();
+        let env = temp.path().join("env");
+        std::fs::create_dir_all(env.join("conda-meta")).unwrap();
+        std::fs::write(env.join(CONDA_META_HISTORY), "").unwrap();
+        std::fs::write(
+            temp.path().join("environment.yml"),
+            "name: ignored\nprefix: ./env\n",
+        )
+        .unwrap();
+
+        assert_eq!(find_conda_env(temp.path()), Some(temp.path().join("env")));
+    }
+
+    #[test]
+    fn find_conda_env_rejects_unvalidated_prefix() {
+        let temp = tempdir().unwrap();
+        std::fs::create_dir(temp.path().join("env")).unwrap();
+        std::fs::write(temp.path().join("environment.yml"), "prefix: ./env\n").unwrap();
+
+        assert_eq!(find_conda_env(temp.path()), None);
+        assert_eq!(
+            env_watch_locations(temp.path()),
+            vec![super::super::EnvLocation::Env(temp.path().join("env"))]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Discovery of all venvs under the git toplevel (for pre-warming).
+
+use std::path::{Path, PathBuf};
+
+/// Directories never searched for venvs
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "__pycache__", "target"];
+
+/// Maximum directory depth below the toplevel
+const MAX_DEPTH: usize = 8;
+
+/// Upper bound on directories visited, so a huge tree cannot stall discovery
+const MAX_VISITED_DIRS: usize = 50_000;
+
+/// Find venvs (configured `venv_dirs` names containing one of `venv_markers`) under `toplevel`.
+///
+/// Blocking; run it off the async runtime. Venvs are not descended into, nor are
+/// hidden directories other than the venv names themselves.
+pub fn discover_venvs(
+    toplevel: &Path,
+    venv_dirs: &[String],
+    venv_markers: &[String],
+) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut stack = vec![(toplevel.to_path_buf(), 0)];
+    let mut visited = 0;
+
+    while let Some((dir, depth)) = stack.pop() {
+        visited += 1;
+        if visited > MAX_VISITED_DIRS {
+            tracing::warn!(
+                toplevel = %toplevel.display(),
+                max_dirs = MAX_VISITED_DIRS,
+                "Venv discovery stopped early: too many directories"
+            );
+            break;
+        }
+
+        if let Some(venv) = super::find_venv_dir(&dir, venv_dirs, venv_markers) {
+            found.push(venv);
+        }
+        if depth >= MAX_DEPTH {
+            continue;
+        }
+
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            // Symlinked directories are skipped (no cycles, no escaping the toplevel)
+            if !entry.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.')
+                || SKIPPED_DIRS.contains(&name.as_ref())
+                || venv_dirs.iter().any(|v| *v == name)
+            {
+                continue;
+            }
+            stack.push((entry.path(), depth + 1));
+        }
+    }
+
+    found.sort();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn make_venv(path: &Path) {
+        fs::create_dir_all(path).unwrap();
+        fs::write(path.join("pyvenv.cfg"), "home = /usr/bin").unwrap();
+    }
+
+    #[test]
+    fn discovers_nested_venvs() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        make_venv(&root.join(".venv"));
+        make_venv(&root.join("services/api/.venv"));
+        make_venv(&root.join("libs/core/venv"));
+        // Not searched: inside node_modules, hidden dirs, or another venv
+        make_venv(&root.join("web/node_modules/pkg/.venv"));
+        make_venv(&root.join(".cache/tool/.venv"));
+        make_venv(&root.join(".venv/lib/nested/.venv"));
+
+        let venv_dirs = vec![".venv".to_string(), "venv".to_string()];
+        assert_eq!(
+            discover_venvs(root, &venv_dirs, &["pyvenv.cfg".to_string()]),
+            vec![
+                root.join(".venv"),
+                root.join("libs/core/venv"),
+                root.join("services/api/.venv"),
+            ]
+        );
+    }
+}
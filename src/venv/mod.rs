@@ -0,0 +1,662 @@
+use crate::backend::BackendKind;
+use crate::config::{ActivatedEnvMode, Config};
+use crate::error::VenvError;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+mod conda;
+mod discovery;
+mod poetry;
+mod uv;
+
+pub use conda::is_conda_env;
+pub use discovery::discover_venvs;
+
+/// Execute git rev-parse --show-toplevel and get result
+pub async fn get_git_toplevel(working_dir: &Path) -> Result<Option<PathBuf>, VenvError> {
+    let output = match Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(working_dir)
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            tracing::warn!(error = ?e, "git command failed (git not installed or not executable), continuing without git");
+            return Ok(None);
+        }
+    };
+
+    if output.status.success() {
+        let path_str = String::from_utf8_lossy(&output.stdout);
+        let path = PathBuf::from(path_str.trim());
+        tracing::info!(toplevel = %path.display(), "Git toplevel found");
+        Ok(Some(path))
+    } else {
+        tracing::warn!("Not in a git repository");
+        Ok(None)
+    }
+}
+
+/// Search for .venv by traversing parent directories from file path
+///
+/// `.typemux.toml` venv rules are evaluated first; a matching rule whose env
+/// does not exist is an error (never silently falls through to the walk).
+/// Next, files inside a uv workspace member use the workspace root venv.
+/// At each level of the walk, the configured venv directory names
+/// (`<name>/<marker>`, default `.venv/pyvenv.cfg`) are checked first, then a Poetry or
+/// conda env declared by the directory's project files. In catch-all mode the
+/// activated env is returned when the walk finds nothing.
+///
+/// # Arguments
+/// * `file_path` - Starting file path
+/// * `git_toplevel` - Search boundary (if None, search up to root)
+/// * `config` - Project config providing venv rules and venv directory names
+pub async fn find_venv(
+    file_path: &Path,
+    git_toplevel: Option<&Path>,
+    config: &Config,
+) -> Result<Option<PathBuf>, VenvError> {
+    tracing::debug!(
+        file = %file_path.display(),
+        toplevel = ?git_toplevel.map(|p| p.display().to_string()),
+        "Starting .venv search"
+    );
+
+    if let Some(toplevel) = git_toplevel {
+        if let Some(rule) = config.match_venv_rule(file_path, toplevel) {
+            let venv_path = toplevel.join(&rule.venv);
+            if env_exists(&venv_path, &config.venv_markers) {
+                tracing::info!(
+                    venv = %venv_path.display(),
+                    pattern = rule.pattern.as_str(),
+                    "venv found via config rule"
+                );
+                return Ok(Some(venv_path));
+            }
+            return Err(VenvError::MissingRuleTarget {
+                pattern: rule.pattern.as_str().to_string(),
+                venv: venv_path,
+            });
+        }
+    }
+
+    // uv workspace members share the workspace root venv
+    if config.prefer_uv_workspace_venv {
+        if let Some(workspace) = uv::find_workspace(file_path, git_toplevel) {
+            if workspace.contains_member_file(file_path) {
+                if let Some(venv_path) =
+                    find_venv_dir(&workspace.root, &config.venv_dirs, &config.venv_markers)
+                {
+                    tracing::info!(
+                        venv = %venv_path.display(),
+                        workspace_root = %workspace.root.display(),
+                        "uv workspace member, using workspace root venv"
+                    );
+                    return Ok(Some(venv_path));
+                }
+                tracing::debug!(
+                    workspace_root = %workspace.root.display(),
+                    "uv workspace root has no venv, falling back to parent walk"
+                );
+            }
+        }
+    }
+
+    // Start from file's parent directory
+    let mut current = file_path.parent();
+    let mut depth = 0;
+
+    while let Some(dir) = current {
+        tracing::trace!(
+            depth = depth,
+            dir = %dir.display(),
+            "Searching for .venv"
+        );
+
+        // Stop if we exceed git toplevel
+        if let Some(toplevel) = git_toplevel {
+            if !dir.starts_with(toplevel) {
+                tracing::debug!(
+                    dir = %dir.display(),
+                    toplevel = %toplevel.display(),
+                    "Reached git toplevel boundary"
+                );
+                break;
+            }
+        }
+
+        // Check for <venv_dir>/<marker> existence (configured names, in order)
+        if let Some(venv_path) = find_venv_dir(dir, &config.venv_dirs, &config.venv_markers) {
+            tracing::info!(
+                venv = %venv_path.display(),
+                depth = depth,
+                ".venv found"
+            );
+            return Ok(Some(venv_path));
+        }
+
+        // Check for a tool-managed env (may live outside the project tree)
+        if let Some((env, tool)) = find_managed_env(dir, &config.venv_markers) {
+            tracing::info!(
+                venv = %env.display(),
+                project = %dir.display(),
+                tool = tool,
+                depth = depth,
+                "Managed env found"
+            );
+            return Ok(Some(env));
+        }
+
+        // Move to parent directory
+        current = dir.parent();
+        depth += 1;
+    }
+
+    if config.activated_env == ActivatedEnvMode::CatchAll {
+        if let Some((env, source)) = activated_env(&config.venv_markers) {
+            tracing::info!(
+                file = %file_path.display(),
+                venv = %env.display(),
+                source = source,
+                "No .venv ancestor, using activated env (catch-all)"
+            );
+            return Ok(Some(env));
+        }
+    }
+
+    tracing::warn!(
+        file = %file_path.display(),
+        depth = depth,
+        "No .venv found"
+    );
+    Ok(None)
+}
+
+/// A place where an env may be created, watched so that `find_venv` is re-run when it appears
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EnvLocation {
+    /// The env directory itself
+    Env(PathBuf),
+    /// A directory whose children are envs (Poetry's `virtualenvs` directory)
+    Container(PathBuf),
+}
+
+/// Env locations `find_venv` may resolve the documents at `file_paths` to, besides
+/// `venv_dirs` inside their ancestors: matching `.typemux.toml` rule targets and the
+/// Poetry / conda envs of projects up to `git_toplevel` (which may live outside the tree)
+pub fn env_locations<'a>(
+    file_paths: impl Iterator<Item = &'a Path>,
+    git_toplevel: &Path,
+    config: &Config,
+) -> HashSet<EnvLocation> {
+    let mut locations = HashSet::new();
+    let mut visited = HashSet::new();
+    for file_path in file_paths {
+        if let Some(rule) = config.match_venv_rule(file_path, git_toplevel) {
+            locations.insert(EnvLocation::Env(git_toplevel.join(&rule.venv)));
+        }
+
+        let mut current = file_path.parent();
+        while let Some(dir) = current {
+            if !dir.starts_with(git_toplevel) || !visited.insert(dir.to_path_buf()) {
+                break;
+            }
+            locations.extend(poetry::env_watch_location(dir));
+            locations.extend(conda::env_watch_locations(dir));
+            current = dir.parent();
+        }
+    }
+    locations
+}
+
+/// Search for fallback env (.venv search from cwd at startup)
+pub async fn find_fallback_venv(cwd: &Path, config: &Config) -> Result<Option<PathBuf>, VenvError> {
+    tracing::info!(cwd = %cwd.display(), "Searching for fallback .venv");
+
+    // 0. Env activated in the launching shell (opt-in)
+    if config.activated_env != ActivatedEnvMode::Ignore {
+        if let Some((env, source)) = activated_env(&config.venv_markers) {
+            tracing::info!(
+                venv = %env.display(),
+                source = source,
+                "Fallback env found from activated environment"
+            );
+            return Ok(Some(env));
+        }
+        tracing::debug!("No valid activated environment (VIRTUAL_ENV / CONDA_PREFIX)");
+    }
+
+    // 1. Get git toplevel
+    let git_toplevel = get_git_toplevel(cwd).await?;
+
+    // 2. Search for .venv from toplevel
+    if let Some(toplevel) = &git_toplevel {
+        tracing::debug!(
+            toplevel = %toplevel.display(),
+            venv_dirs = ?config.venv_dirs,
+            "Checking git toplevel for .venv"
+        );
+
+        if let Some(venv_path) = find_venv_dir(toplevel, &config.venv_dirs, &config.venv_markers) {
+            tracing::info!(
+                venv = %venv_path.display(),
+                "Fallback .venv found at git toplevel"
+            );
+            return Ok(Some(venv_path));
+        }
+
+        if let Some((env, tool)) = find_managed_env(toplevel, &config.venv_markers) {
+            tracing::info!(
+                venv = %env.display(),
+                tool = tool,
+                "Fallback managed env found at git toplevel"
+            );
+            return Ok(Some(env));
+        }
+    } else {
+        tracing::debug!("No git toplevel found, skipping toplevel check");
+    }
+
+    // 3. Search for .venv from cwd
+    tracing::debug!(
+        cwd = %cwd.display(),
+        venv_dirs = ?config.venv_dirs,
+        "Checking cwd for .venv"
+    );
+
+    if let Some(venv_path) = find_venv_dir(cwd, &config.venv_dirs, &config.venv_markers) {
+        tracing::info!(
+            venv = %venv_path.display(),
+            "Fallback .venv found at cwd"
+        );
+        return Ok(Some(venv_path));
+    }
+
+    if let Some((env, tool)) = find_managed_env(cwd, &config.venv_markers) {
+        tracing::info!(
+            venv = %env.display(),
+            tool = tool,
+            "Fallback managed env found at cwd"
+        );
+        return Ok(Some(env));
+    }
+
+    tracing::warn!(
+        cwd = %cwd.display(),
+        git_toplevel = ?git_toplevel.as_ref().map(|p| p.display().to_string()),
+        "No fallback .venv found"
+    );
+    Ok(None)
+}
+
+/// Choose the LSP backend for a venv:
+/// 1. The first matching `.typemux.toml` backend rule
+/// 2. `[tool.typemux] backend` in the nearest `pyproject.toml` declaring one, searched
+///    from the venv's directory up to the git toplevel (so every member of a workspace
+///    sharing the root `.venv` gets the root's choice). For an env outside the tree
+///    (Poetry / conda), the search starts at `project_dir`, the project using it.
+/// 3. `default` (`--backend` / `TYPEMUX_CC_BACKEND`)
+pub fn resolve_backend_kind(
+    venv: &Path,
+    project_dir: Option<&Path>,
+    git_toplevel: Option<&Path>,
+    config: &Config,
+    default: BackendKind,
+) -> BackendKind {
+    if let Some(rule) = config.match_backend_rule(venv, git_toplevel) {
+        tracing::debug!(
+            venv = %venv.display(),
+            pattern = rule.venv.as_str(),
+            backend = rule.backend.display_name(),
+            "Backend selected via config rule"
+        );
+        return rule.backend;
+    }
+
+    let in_tree_dir = venv
+        .parent()
+        .filter(|dir| git_toplevel.map_or(true, |toplevel| dir.starts_with(toplevel)));
+    let mut current = in_tree_dir.or(project_dir);
+    while let Some(dir) = current {
+        if git_toplevel.is_some_and(|toplevel| !dir.starts_with(toplevel)) {
+            break;
+        }
+        let pyproject_path = dir.join("pyproject.toml");
+        if let Some(value) = read_toml(&pyproject_path).and_then(|pyproject| {
+            pyproject
+                .get("tool")?
+                .get("typemux")?
+                .get("backend")
+                .cloned()
+        }) {
+            match value.try_into::<BackendKind>() {
+                Ok(kind) => {
+                    tracing::debug!(
+                        venv = %venv.display(),
+                        pyproject = %pyproject_path.display(),
+                        backend = kind.display_name(),
+                        "Backend selected via [tool.typemux] in pyproject.toml"
+                    );
+                    return kind;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        pyproject = %pyproject_path.display(),
+                        error = %e,
+                        "Ignoring invalid [tool.typemux] backend in pyproject.toml"
+                    );
+                }
+            }
+        }
+        current = dir.parent();
+    }
+
+    default
+}
+
+/// Check whether `path` is still a usable env (a venv with one of `venv_markers`,
+/// e.g. `pyvenv.cfg`, or a conda env)
+pub fn env_exists(path: &Path, venv_markers: &[String]) -> bool {
+    is_venv_dir(path, venv_markers) || is_conda_env(path)
+}
+
+/// Whether `path` contains one of `venv_markers`
+pub fn is_venv_dir(path: &Path, venv_markers: &[String]) -> bool {
+    venv_markers.iter().any(|marker| path.join(marker).exists())
+}
+
+/// The env activated in the shell that launched the proxy.
+/// `VIRTUAL_ENV` (must be an env, see `env_exists`) wins over `CONDA_PREFIX` (must be a conda env).
+/// Returns the env path and the variable it came from.
+fn activated_env(venv_markers: &[String]) -> Option<(PathBuf, &'static str)> {
+    let from_var = |var: &'static str, is_valid: &dyn Fn(&Path) -> bool| {
+        let path = PathBuf::from(std::env::var_os(var).filter(|v| !v.is_empty())?);
+        if is_valid(&path) {
+            Some((path, var))
+        } else {
+            tracing::debug!(var = var, path = %path.display(), "Ignoring invalid activated env");
+            None
+        }
+    };
+
+    from_var("VIRTUAL_ENV", &|p| env_exists(p, venv_markers))
+        .or_else(|| from_var("CONDA_PREFIX", &is_conda_env))
+}
+
+/// Find the first of `venv_dirs` (in priority order) that is a venv inside `dir`.
+/// If several exist, the earliest entry wins; the others are logged and ignored.
+fn find_venv_dir(dir: &Path, venv_dirs: &[String], venv_markers: &[String]) -> Option<PathBuf> {
+    let mut found = venv_dirs
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|venv_path| is_venv_dir(venv_path, venv_markers));
+
+    let chosen = found.next()?;
+    let ignored: Vec<PathBuf> = found.collect();
+    if !ignored.is_empty() {
+        tracing::debug!(
+            chosen = %chosen.display(),
+            ignored = ?ignored,
+            "Multiple venv directories found, using the highest-priority one"
+        );
+    }
+    Some(chosen)
+}
+
+/// Check a project directory for an env managed by Poetry or conda.
+/// Returns the env path and the managing tool name (for logging).
+fn find_managed_env(dir: &Path, venv_markers: &[String]) -> Option<(PathBuf, &'static str)> {
+    if let Some(env) = poetry::find_poetry_env(dir, venv_markers) {
+        return Some((env, "poetry"));
+    }
+    if let Some(env) = conda::find_conda_env(dir) {
+        return Some((env, "conda"));
+    }
+    None
+}
+
+/// Read and parse a TOML file; parse errors are logged and treated as absent
+fn read_toml(path: &Path) -> Option<toml::Table> {
+    let content = std::fs::read_to_string(path).ok()?;
+    match content.parse::<toml::Table>() {
+        Ok(table) => Some(table),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to parse TOML, ignoring");
+            None
+        }
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Expand a leading `~/` to `$HOME`
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use tokio::fs;
+
+    #[tokio::test]
+    async fn test_find_venv() {
+        let temp = tempdir().unwrap();
+        let venv = temp.path().join(".venv");
+        fs::create_dir(&venv).await.unwrap();
+        fs::write(venv.join("pyvenv.cfg"), "home = /usr/bin")
+            .await
+            .unwrap();
+
+        let subdir = temp.path().join("subdir");
+        fs::create_dir(&subdir).await.unwrap();
+        let file = subdir.join("test.py");
+        fs::write(&file, "# test").await.unwrap();
+
+        let result = find_venv(&file, None, &Config::default()).await.unwrap();
+        assert_eq!(result, Some(venv));
+    }
+
+    #[tokio::test]
+    async fn test_find_venv_not_found() {
+        let temp = tempdir().unwrap();
+        let file = temp.path().join("test.py");
+        fs::write(&file, "# test").await.unwrap();
+
+        let result = find_venv(&file, None, &Config::default()).await.unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn test_find_venv_configured_dir_names() {
+        let temp = tempdir().unwrap();
+        for name in ["env", "venv"] {
+            let venv = temp.path().join(name);
+            fs::create_dir(&venv).await.unwrap();
+            fs::write(venv.join("pyvenv.cfg"), "home = /usr/bin")
+                .await
+                .unwrap();
+        }
+        let file = temp.path().join("test.py");
+        fs::write(&file, "# test").await.unwrap();
+
+        // Default (.venv only) ignores other names
+        let result = find_venv(&file, None, &Config::default()).await.unwrap();
+        assert_eq!(result, None);
+
+        // Earliest configured name wins when several exist
+        let config: Config = toml::from_str(r#"venv_dirs = [".venv", "venv", "env"]"#).unwrap();
+        let result = find_venv(&file, None, &config).await.unwrap();
+        assert_eq!(result, Some(temp.path().join("venv")));
+    }
+
+    #[tokio::test]
+    async fn test_find_venv_custom_markers() {
+        let temp = tempdir().unwrap();
+        // A venv without pyvenv.cfg (e.g. built by a tool that only leaves its own marker)
+        let venv = temp.path().join(".venv");
+        fs::create_dir_all(&venv).await.unwrap();
+        fs::write(venv.join(".tool-env"), "").await.unwrap();
+        let file = temp.path().join("test.py");
+        fs::write(&file, "# test").await.unwrap();
+
+        let result = find_venv(&file, None, &Config::default()).await.unwrap();
+        assert_eq!(result, None);
+
+        let config: Config =
+            toml::from_str(r#"venv_markers = ["pyvenv.cfg", ".tool-env"]"#).unwrap();
+        let result = find_venv(&file, None, &config).await.unwrap();
+        assert_eq!(result, Some(venv.clone()));
+        assert!(env_exists(&venv, &config.venv_markers));
+        assert!(!env_exists(&venv, &Config::default().venv_markers));
+    }
+
+    #[tokio::test]
+    async fn test_find_venv_uv_workspace_member() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("pyproject.toml"),
+            "[tool.uv.workspace]\nmembers = [\"packages/*\"]\n",
+        )
+        .await
+        .unwrap();
+        let member = temp.path().join("packages/a");
+        for venv in [temp.path().join(".venv"), member.join(".venv")] {
+            fs::create_dir_all(&venv).await.unwrap();
+            fs::write(venv.join("pyvenv.cfg"), "home = /usr/bin")
+                .await
+                .unwrap();
+        }
+        let file = member.join("main.py");
+        fs::write(&file, "# test").await.unwrap();
+
+        // Workspace root venv wins over the member's stale .venv
+        let result = find_venv(&file, Some(temp.path()), &Config::default())
+            .await
+            .unwrap();
+        assert_eq!(result, Some(temp.path().join(".venv")));
+
+        // Override knob restores nearest-ancestor behavior
+        let config = Config {
+            prefer_uv_workspace_venv: false,
+            ..Config::default()
+        };
+        let result = find_venv(&file, Some(temp.path()), &config).await.unwrap();
+        assert_eq!(result, Some(member.join(".venv")));
+    }
+
+    #[test]
+    fn test_resolve_backend_kind_precedence() {
+        let temp = tempdir().unwrap();
+        let venv = temp.path().join("ext/.venv");
+        let project = temp.path().join("ext/src/pkg");
+        std::fs::create_dir_all(&venv).unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+        let resolve = |config: &Config| {
+            resolve_backend_kind(
+                &venv,
+                Some(&project),
+                Some(temp.path()),
+                config,
+                BackendKind::Pyright,
+            )
+        };
+
+        assert_eq!(resolve(&Config::default()), BackendKind::Pyright);
+
+        std::fs::write(
+            temp.path().join("ext/pyproject.toml"),
+            "[tool.typemux]\nbackend = \"ty\"\n",
+        )
+        .unwrap();
+        assert_eq!(resolve(&Config::default()), BackendKind::Ty);
+
+        // A member project's own choice does not apply to the venv it shares
+        std::fs::write(
+            project.join("pyproject.toml"),
+            "[tool.typemux]\nbackend = \"pyrefly\"\n",
+        )
+        .unwrap();
+        assert_eq!(resolve(&Config::default()), BackendKind::Ty);
+
+        let config: Config =
+            toml::from_str("[[backend_rules]]\nvenv = \"ext/*\"\nbackend = \"pyrefly\"\n").unwrap();
+        assert_eq!(resolve(&config), BackendKind::Pyrefly);
+    }
+
+    #[test]
+    fn test_resolve_backend_kind_from_project_of_out_of_tree_env() {
+        let temp = tempdir().unwrap();
+        let top = temp.path().join("repo");
+        let project = top.join("services/api");
+        std::fs::create_dir_all(&project).unwrap();
+        // e.g. a Poetry env in the cache directory
+        let env = temp.path().join("cache/virtualenvs/api-AbCdEfGh-py3.12");
+        std::fs::create_dir_all(&env).unwrap();
+        std::fs::write(
+            project.join("pyproject.toml"),
+            "[tool.poetry]\nname = \"api\"\n\n[tool.typemux]\nbackend = \"ty\"\n",
+        )
+        .unwrap();
+        // Outside the toplevel: never consulted
+        std::fs::write(
+            temp.path().join("pyproject.toml"),
+            "[tool.typemux]\nbackend = \"pyrefly\"\n",
+        )
+        .unwrap();
+
+        let resolve = |dir: Option<&Path>| {
+            resolve_backend_kind(
+                &env,
+                dir,
+                Some(&top),
+                &Config::default(),
+                BackendKind::Pyright,
+            )
+        };
+        assert_eq!(resolve(Some(&project.join("app"))), BackendKind::Ty);
+        assert_eq!(resolve(Some(&top.join("scripts"))), BackendKind::Pyright);
+        assert_eq!(resolve(None), BackendKind::Pyright);
+    }
+
+    #[tokio::test]
+    async fn test_find_venv_config_rule() {
+        let temp = tempdir().unwrap();
+        let shared = temp.path().join("tools/envs/services-venv");
+        fs::create_dir_all(&shared).await.unwrap();
+        fs::write(shared.join("pyvenv.cfg"), "home = /usr/bin")
+            .await
+            .unwrap();
+        let service = temp.path().join("services/api");
+        fs::create_dir_all(&service).await.unwrap();
+        let file = service.join("main.py");
+        fs::write(&file, "# test").await.unwrap();
+
+        let config: Config = toml::from_str(
+            "[[venv_rules]]\npattern = \"services/*\"\nvenv = \"tools/envs/services-venv\"\n",
+        )
+        .unwrap();
+
+        let result = find_venv(&file, Some(temp.path()), &config).await.unwrap();
+        assert_eq!(result, Some(shared.clone()));
+
+        // Rule pointing at a missing env is an error, not a silent fall-through
+        fs::remove_file(shared.join("pyvenv.cfg")).await.unwrap();
+        let result = find_venv(&file, Some(temp.path()), &config).await;
+        assert!(matches!(result, Err(VenvError::MissingRuleTarget { .. })));
+
+        // The target is watched so its creation is noticed
+        let locations = env_locations([file.as_path()].into_iter(), temp.path(), &config);
+        assert!(locations.contains(&EnvLocation::Env(shared.clone())));
+    }
+}
@@ -0,0 +1,407 @@
+//! Poetry-managed virtualenv resolution.
+//!
+//! Poetry keeps environments outside the project tree by default
+//! (`~/.cache/pypoetry/virtualenvs/<name>-<hash>-py3.x`), so they are never
+//! found by the `.venv` parent walk. This module reproduces Poetry's
+//! settings lookup and env-name hashing to locate them.
+
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+const PYPROJECT_TOML: &str = "pyproject.toml";
+const POETRY_TOML: &str = "poetry.toml";
+const POETRY_LOCK: &str = "poetry.lock";
+const ENVS_TOML: &str = "envs.toml";
+
+/// Poetry's fallback package name for projects without a name (`package-mode = false`)
+const NON_PACKAGE_MODE_NAME: &str = "non-package-mode";
+
+/// Maximum length of the sanitized name part of an env name (matches Poetry)
+const MAX_NAME_LEN: usize = 42;
+
+/// Effective `virtualenvs.*` settings for a project
+#[derive(Debug, Default, PartialEq)]
+struct PoetrySettings {
+    in_project: Option<bool>,
+    path: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl PoetrySettings {
+    /// Fill unset values from `other` (lower precedence source)
+    fn or(self, other: PoetrySettings) -> PoetrySettings {
+        PoetrySettings {
+            in_project: self.in_project.or(other.in_project),
+            path: self.path.or(other.path),
+            cache_dir: self.cache_dir.or(other.cache_dir),
+        }
+    }
+}
+
+/// Where a Poetry project's env lives, whether or not it exists yet
+enum PoetryEnvLocation {
+    /// `virtualenvs.in-project`: `<project>/.venv`
+    InProject(PathBuf),
+    /// `<virtualenvs_dir>/<env_name>-py<X.Y>`
+    Cache {
+        virtualenvs_dir: PathBuf,
+        env_name: String,
+    },
+}
+
+/// Resolve the Poetry-managed environment for a project directory.
+///
+/// Returns None if `project_dir` is not a Poetry project or its env does not exist yet
+/// (no directory with one of `venv_markers`).
+pub(super) fn find_poetry_env(project_dir: &Path, venv_markers: &[String]) -> Option<PathBuf> {
+    match env_location(project_dir)? {
+        PoetryEnvLocation::InProject(venv) => {
+            if super::is_venv_dir(&venv, venv_markers) {
+                return Some(venv);
+            }
+            tracing::debug!(
+                project = %project_dir.display(),
+                "Poetry virtualenvs.in-project is set but .venv does not exist"
+            );
+            None
+        }
+        PoetryEnvLocation::Cache {
+            virtualenvs_dir,
+            env_name,
+        } => {
+            tracing::debug!(
+                project = %project_dir.display(),
+                virtualenvs_dir = %virtualenvs_dir.display(),
+                env_name = %env_name,
+                "Searching for Poetry env"
+            );
+            select_env(&virtualenvs_dir, &env_name, venv_markers)
+        }
+    }
+}
+
+/// Where the env of the Poetry project in `project_dir` appears once created:
+/// the in-project `.venv`, or the `virtualenvs` directory holding one env per python version
+pub(super) fn env_watch_location(project_dir: &Path) -> Option<super::EnvLocation> {
+    Some(match env_location(project_dir)? {
+        PoetryEnvLocation::InProject(venv) => super::EnvLocation::Env(venv),
+        PoetryEnvLocation::Cache {
+            virtualenvs_dir, ..
+        } => super::EnvLocation::Container(virtualenvs_dir),
+    })
+}
+
+/// Locate a project's env from its settings. Returns None if `project_dir` is not a Poetry project.
+fn env_location(project_dir: &Path) -> Option<PoetryEnvLocation> {
+    let pyproject_path = project_dir.join(PYPROJECT_TOML);
+    if !pyproject_path.exists() {
+        return None;
+    }
+
+    let pyproject = super::read_toml(&pyproject_path)?;
+    if !is_poetry_project(&pyproject, project_dir) {
+        return None;
+    }
+
+    // Precedence: environment variables > project poetry.toml > global config.toml
+    let settings = settings_from_env()
+        .or(super::read_toml(&project_dir.join(POETRY_TOML))
+            .map(|t| settings_from_toml(&t, project_dir))
+            .unwrap_or_default())
+        .or(global_config_dir()
+            .and_then(|dir| {
+                let path = dir.join("config.toml");
+                super::read_toml(&path).map(|t| settings_from_toml(&t, &dir))
+            })
+            .unwrap_or_default());
+
+    if settings.in_project == Some(true) {
+        return Some(PoetryEnvLocation::InProject(project_dir.join(".venv")));
+    }
+
+    let cache_dir = settings.cache_dir.or_else(default_cache_dir)?;
+    let virtualenvs_dir = match settings.path {
+        Some(path) => expand_cache_dir(&path, &cache_dir),
+        None => cache_dir.join("virtualenvs"),
+    };
+
+    let canonical_project = std::fs::canonicalize(project_dir).ok()?;
+    let name = package_name(&pyproject);
+    let env_name = generate_env_name(&name, &canonical_project.to_string_lossy());
+
+    Some(PoetryEnvLocation::Cache {
+        virtualenvs_dir,
+        env_name,
+    })
+}
+
+/// A project is managed by Poetry if it has a `[tool.poetry]` table or a `poetry.lock`.
+fn is_poetry_project(pyproject: &toml::Table, project_dir: &Path) -> bool {
+    pyproject
+        .get("tool")
+        .and_then(|t| t.get("poetry"))
+        .is_some()
+        || project_dir.join(POETRY_LOCK).exists()
+}
+
+/// Package name as Poetry sees it (`[tool.poetry] name`, then `[project] name`)
+fn package_name(pyproject: &toml::Table) -> String {
+    pyproject
+        .get("tool")
+        .and_then(|t| t.get("poetry"))
+        .and_then(|p| p.get("name"))
+        .or_else(|| pyproject.get("project").and_then(|p| p.get("name")))
+        .and_then(|n| n.as_str())
+        .map(canonicalize_name)
+        .unwrap_or_else(|| NON_PACKAGE_MODE_NAME.to_string())
+}
+
+/// PEP 503 name normalization (lowercase, runs of `-_.` collapsed to `-`)
+fn canonicalize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut prev_sep = false;
+    for ch in name.chars() {
+        if matches!(ch, '-' | '_' | '.') {
+            if !prev_sep {
+                out.push('-');
+            }
+            prev_sep = true;
+        } else {
+            out.extend(ch.to_lowercase());
+            prev_sep = false;
+        }
+    }
+    out
+}
+
+/// Reproduce `EnvManager.generate_env_name`: `<sanitized-name>-<first 8 chars of
+/// urlsafe-base64(sha256(cwd))>`. The python version suffix is appended separately.
+fn generate_env_name(name: &str, cwd: &str) -> String {
+    let sanitized: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            ' ' | '$' | '`' | '!' | '*' | '@' | '"' | '\\' | '\r' | '\n' | '\t' => '_',
+            c => c,
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+
+    let digest = Sha256::digest(cwd.as_bytes());
+    let encoded = URL_SAFE.encode(digest);
+
+    format!("{}-{}", sanitized, &encoded[..8])
+}
+
+/// Pick the env directory for `env_name` inside `virtualenvs_dir`.
+///
+/// Uses the python version recorded in `envs.toml` (set by `poetry env use`) when
+/// available, otherwise the highest python version among existing candidates.
+fn select_env(virtualenvs_dir: &Path, env_name: &str, venv_markers: &[String]) -> Option<PathBuf> {
+    if let Some(minor) = super::read_toml(&virtualenvs_dir.join(ENVS_TOML)).and_then(|t| {
+        t.get(env_name)
+            .and_then(|e| e.get("minor"))
+            .and_then(|m| m.as_str())
+            .map(|m| m.to_string())
+    }) {
+        let env = virtualenvs_dir.join(format!("{}-py{}", env_name, minor));
+        if super::is_venv_dir(&env, venv_markers) {
+            return Some(env);
+        }
+    }
+
+    let prefix = format!("{}-py", env_name);
+    let entries = std::fs::read_dir(virtualenvs_dir).ok()?;
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let version = parse_python_version(file_name.to_str()?.strip_prefix(&prefix)?)?;
+            let path = entry.path();
+            super::is_venv_dir(&path, venv_markers).then_some((version, path))
+        })
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, path)| path)
+}
+
+/// Parse `3.11` into `(3, 11)`
+fn parse_python_version(s: &str) -> Option<(u32, u32)> {
+    let (major, minor) = s.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Read `POETRY_VIRTUALENVS_IN_PROJECT`, `POETRY_VIRTUALENVS_PATH` and `POETRY_CACHE_DIR`
+fn settings_from_env() -> PoetrySettings {
+    PoetrySettings {
+        in_project: std::env::var("POETRY_VIRTUALENVS_IN_PROJECT")
+            .ok()
+            .and_then(|v| parse_bool(&v)),
+        path: std::env::var_os("POETRY_VIRTUALENVS_PATH").map(PathBuf::from),
+        cache_dir: std::env::var_os("POETRY_CACHE_DIR").map(PathBuf::from),
+    }
+}
+
+/// Extract settings from a `poetry.toml` / `config.toml` table.
+/// Relative paths are resolved against `base_dir`.
+fn settings_from_toml(table: &toml::Table, base_dir: &Path) -> PoetrySettings {
+    let virtualenvs = table.get("virtualenvs");
+    let resolve = |p: &str| {
+        let path = super::expand_home(p);
+        if path.is_absolute() || p.starts_with("{cache-dir}") {
+            path
+        } else {
+            base_dir.join(path)
+        }
+    };
+
+    PoetrySettings {
+        in_project: virtualenvs
+            .and_then(|v| v.get("in-project"))
+            .and_then(|v| v.as_bool()),
+        path: virtualenvs
+            .and_then(|v| v.get("path"))
+            .and_then(|v| v.as_str())
+            .map(resolve),
+        cache_dir: table.get("cache-dir").and_then(|v| v.as_str()).map(resolve),
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Substitute the `{cache-dir}` placeholder Poetry allows in `virtualenvs.path`
+fn expand_cache_dir(path: &Path, cache_dir: &Path) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix("{cache-dir}")) {
+        Some(rest) => cache_dir.join(rest.trim_start_matches('/')),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Poetry's global config directory (`POETRY_CONFIG_DIR` or platform default)
+fn global_config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("POETRY_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(target_os = "macos") {
+        return super::home_dir().map(|h| h.join("Library/Application Support/pypoetry"));
+    }
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| super::home_dir().map(|h| h.join(".config")))
+        .map(|d| d.join("pypoetry"))
+}
+
+/// Poetry's default cache directory (platform default)
+fn default_cache_dir() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        return super::home_dir().map(|h| h.join("Library/Caches/pypoetry"));
+    }
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| super::home_dir().map(|h| h.join(".cache")))
+        .map(|d| d.join("pypoetry"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn env_name_matches_poetry_hashing() {
+        assert_eq!(
+            generate_env_name("my-project", "/home/user/src/my-project"),
+            "my-project-AGEtnDPB"
+        );
+        assert_eq!(
+            generate_env_name(NON_PACKAGE_MODE_NAME, "/tmp/x"),
+            "non-package-mode-LlaqNvU4"
+        );
+    }
+
+    #[test]
+    fn canonicalize_name_collapses_separators() {
+        assert_eq!(canonicalize_name("My_Project"), "my-project");
+        assert_eq!(canonicalize_name("a.-_b"), "a-b");
+    }
+
+    #[test]
+    fn find_poetry_env_with_project_path_setting() {
+        let temp = tempdir().unwrap();
+        let project = temp.path().join("proj");
+        std::fs::create_dir(&project).unwrap();
+        std::fs::write(
+            project.join(PYPROJECT_TOML),
+            "[tool.poetry]\nname = \"Demo_App\"\n",
+        )
+        .unwrap();
+        let envs = temp.path().join("envs");
+        std::fs::write(
+            project.join(POETRY_TOML),
+            format!("[virtualenvs]\npath = \"{}\"\n", envs.display()),
+        )
+        .unwrap();
+
+        let canonical = std::fs::canonicalize(&project).unwrap();
+        let env_name = generate_env_name("demo-app", &canonical.to_string_lossy());
+        for minor in ["3.9", "3.11"] {
+            let env = envs.join(format!("{}-py{}", env_name, minor));
+            std::fs::create_dir_all(&env).unwrap();
+            std::fs::write(env.join("pyvenv.cfg"), "home = /usr/bin").unwrap();
+        }
+
+        assert_eq!(
+            env_watch_location(&project),
+            Some(super::super::EnvLocation::Container(envs.clone()))
+        );
+        let markers = crate::config::Config::default().venv_markers;
+        let found = find_poetry_env(&project, &markers);
+        assert_eq!(found, Some(envs.join(format!("{}-py3.11", env_name))));
+    }
+
+    #[test]
+    fn find_poetry_env_uses_configured_markers() {
+        let temp = tempdir().unwrap();
+        let project = temp.path();
+        std::fs::write(
+            project.join(PYPROJECT_TOML),
+            "[tool.poetry]\nname = \"demo\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            project.join(POETRY_TOML),
+            "[virtualenvs]\nin-project = true\n",
+        )
+        .unwrap();
+        let venv = project.join(".venv");
+        std::fs::create_dir(&venv).unwrap();
+        std::fs::write(venv.join(".tool-env"), "").unwrap();
+
+        let default_markers = crate::config::Config::default().venv_markers;
+        assert_eq!(find_poetry_env(project, &default_markers), None);
+        let markers = vec![".tool-env".to_string()];
+        assert_eq!(find_poetry_env(project, &markers), Some(venv));
+    }
+
+    #[test]
+    fn find_poetry_env_ignores_non_poetry_project() {
+        let temp = tempdir().unwrap();
+        std::fs::write(
+            temp.path().join(PYPROJECT_TOML),
+            "[project]\nname = \"plain\"\n",
+        )
+        .unwrap();
+
+        let markers = crate::config::Config::default().venv_markers;
+        assert_eq!(find_poetry_env(temp.path(), &markers), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! uv workspace awareness.
+//!
+//! In a uv workspace, member packages share the workspace root's venv. A stale
+//! `.venv` inside a member would otherwise win the nearest-ancestor walk and
+//! spawn a second backend with the wrong dependencies.
+
+use glob::{MatchOptions, Pattern};
+use std::path::{Path, PathBuf};
+
+const PYPROJECT_TOML: &str = "pyproject.toml";
+
+/// Member globs match one directory level per `*`, like uv itself
+const MEMBER_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// A uv workspace root (`[tool.uv.workspace]` in its `pyproject.toml`)
+#[derive(Debug)]
+pub(super) struct UvWorkspace {
+    pub root: PathBuf,
+    members: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl UvWorkspace {
+    /// Check whether `file_path` lives inside a (non-excluded) workspace member.
+    /// Files belonging to the root package itself are not members.
+    pub fn contains_member_file(&self, file_path: &Path) -> bool {
+        let Some(parent) = file_path.parent() else {
+            return false;
+        };
+        let Ok(relative) = parent.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        // Check every ancestor directory below the root: the member dir may be
+        // several levels above the file (e.g. `packages/a` for `packages/a/src/x.py`)
+        let mut member_dir = PathBuf::new();
+        for component in relative.components() {
+            member_dir.push(component);
+            let matches = |patterns: &[Pattern]| {
+                patterns
+                    .iter()
+                    .any(|p| p.matches_path_with(&member_dir, MEMBER_MATCH_OPTIONS))
+            };
+            if matches(&self.members) && !matches(&self.exclude) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Find the uv workspace enclosing `file_path`, searching parent directories up to
+/// `git_toplevel` (or the filesystem root).
+pub(super) fn find_workspace(file_path: &Path, git_toplevel: Option<&Path>) -> Option<UvWorkspace> {
+    let mut current = file_path.parent();
+    while let Some(dir) = current {
+        if let Some(toplevel) = git_toplevel {
+            if !dir.starts_with(toplevel) {
+                break;
+            }
+        }
+
+        let pyproject_path = dir.join(PYPROJECT_TOML);
+        if pyproject_path.exists() {
+            if let Some(workspace) = super::read_toml(&pyproject_path)
+                .and_then(|pyproject| parse_workspace(&pyproject, dir))
+            {
+                return Some(workspace);
+            }
+        }
+
+        current = dir.parent();
+    }
+    None
+}
+
+/// Parse `[tool.uv.workspace] members / exclude` from a root `pyproject.toml`
+fn parse_workspace(pyproject: &toml::Table, root: &Path) -> Option<UvWorkspace> {
+    let workspace = pyproject.get("tool")?.get("uv")?.get("workspace")?;
+    let globs = |key: &str| -> Vec<Pattern> {
+        workspace
+            .get(key)
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str())
+            .filter_map(|g| match Pattern::new(g.trim_end_matches('/')) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    tracing::warn!(glob = g, error = %e, "Ignoring invalid uv workspace glob");
+                    None
+                }
+            })
+            .collect()
+    };
+
+    Some(UvWorkspace {
+        root: root.to_path_buf(),
+        members: globs("members"),
+        exclude: globs("exclude"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(toml_src: &str) -> UvWorkspace {
+        let table: toml::Table = toml_src.parse().unwrap();
+        parse_workspace(&table, Path::new("/ws")).unwrap()
+    }
+
+    #[test]
+    fn member_files_are_detected() {
+        let ws = workspace(
+            "[tool.uv.workspace]\nmembers = [\"packages/*\", \"apps/web\"]\nexclude = [\"packages/legacy\"]\n",
+        );
+        assert!(ws.contains_member_file(Path::new("/ws/packages/a/src/a/mod.py")));
+        assert!(ws.contains_member_file(Path::new("/ws/apps/web/main.py")));
+        assert!(!ws.contains_member_file(Path::new("/ws/packages/legacy/x.py")));
+        assert!(!ws.contains_member_file(Path::new("/ws/src/root_pkg.py")));
+        assert!(!ws.contains_member_file(Path::new("/other/packages/a/x.py")));
+    }
+
+    #[test]
+    fn non_workspace_pyproject_is_ignored() {
+        let table: toml::Table = "[project]\nname = \"x\"\n".parse().unwrap();
+        assert!(parse_workspace(&table, Path::new("/ws")).is_none());
+    }
+}
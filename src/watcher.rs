@@ -0,0 +1,369 @@
+use crate::venv::EnvLocation;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// File whose appearance/disappearance marks a venv as created/removed
+const PYVENV_CFG: &str = "pyvenv.cfg";
+
+/// Directory whose appearance marks a conda env as created
+const CONDA_META: &str = "conda-meta";
+
+/// Lockfiles next to an in-project venv, rewritten by `uv add` / `poetry add`
+const LOCK_FILES: &[&str] = &["uv.lock", "poetry.lock"];
+
+const SITE_PACKAGES: &str = "site-packages";
+
+/// Notification that a venv may have been created, removed, or had its packages changed
+#[derive(Debug)]
+pub struct WatchEvent {
+    pub paths: Vec<PathBuf>,
+}
+
+/// Filesystem watcher for venv changes (inotify on Linux, FSEvents on macOS).
+///
+/// Only non-recursive watches are used, so huge trees (node_modules, site-packages
+/// contents) never get watched:
+/// - ancestor directories of open documents (a new `.venv` appears as a child)
+/// - existing venv directories inside those (`pyvenv.cfg` appears/disappears)
+/// - `site-packages` of pooled venvs (a package dir / `.dist-info` appears/disappears)
+/// - the directory containing a pooled venv (`uv.lock` / `poetry.lock` rewritten)
+/// - env locations outside the `venv_dirs` names (rule targets, Poetry / conda envs),
+///   their parent directory, and Poetry's `virtualenvs` directory
+///
+/// Events are filtered down to those paths before being sent to the proxy's main loop.
+pub struct VenvWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+    /// Shared with the event filter, which runs on the watcher's thread
+    env_targets: Arc<Mutex<EnvTargets>>,
+}
+
+/// Env locations currently watched (see `venv::env_locations`)
+#[derive(Debug, Default)]
+struct EnvTargets {
+    envs: HashSet<PathBuf>,
+    containers: HashSet<PathBuf>,
+}
+
+impl EnvTargets {
+    fn new(locations: HashSet<EnvLocation>) -> Self {
+        let mut targets = Self::default();
+        for location in locations {
+            match location {
+                EnvLocation::Env(env) => targets.envs.insert(env),
+                EnvLocation::Container(dir) => targets.containers.insert(dir),
+            };
+        }
+        targets
+    }
+
+    /// Whether `path` is a watched env location (or a child of a container)
+    fn is_env_path(&self, path: &Path) -> bool {
+        self.envs.contains(path)
+            || path
+                .parent()
+                .is_some_and(|parent| self.containers.contains(parent))
+    }
+
+    /// Existing directories to watch: containers, envs (markers), and env parents (creation)
+    fn watch_dirs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.containers
+            .iter()
+            .cloned()
+            .chain(self.envs.iter().cloned())
+            .chain(
+                self.envs
+                    .iter()
+                    .filter_map(|env| env.parent().map(Path::to_path_buf)),
+            )
+            .filter(|dir| dir.is_dir())
+    }
+}
+
+impl VenvWatcher {
+    pub fn new(
+        venv_dirs: Vec<String>,
+        venv_markers: Vec<String>,
+        tx: mpsc::Sender<WatchEvent>,
+    ) -> notify::Result<Self> {
+        let env_targets = Arc::new(Mutex::new(EnvTargets::default()));
+        let filter_targets = Arc::clone(&env_targets);
+        let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!(error = ?e, "Filesystem watcher error");
+                    return;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+
+            let paths: Vec<PathBuf> = event
+                .paths
+                .into_iter()
+                .filter(|p| {
+                    is_layout_path(p, &venv_dirs, &venv_markers)
+                        || is_package_path(p)
+                        || filter_targets
+                            .lock()
+                            .is_ok_and(|targets| targets.is_env_path(p))
+                })
+                .collect();
+            if paths.is_empty() {
+                return;
+            }
+
+            // Events are only triggers for a debounced rescan: dropping some when the
+            // channel is full is harmless
+            if tx.try_send(WatchEvent { paths }).is_err() {
+                tracing::trace!("Watch event channel full, dropping event");
+            }
+        })?;
+
+        Ok(Self {
+            watcher,
+            watched: HashSet::new(),
+            env_targets,
+        })
+    }
+
+    /// Whether `path` is one of the env locations passed to the last `sync`
+    /// (or a new env inside a container location)
+    pub fn is_env_path(&self, path: &Path) -> bool {
+        self.env_targets
+            .lock()
+            .is_ok_and(|targets| targets.is_env_path(path))
+    }
+
+    /// Replace the set of watched directories and env locations,
+    /// adding and removing watches as needed.
+    pub fn sync(&mut self, mut dirs: HashSet<PathBuf>, env_locations: HashSet<EnvLocation>) {
+        let targets = EnvTargets::new(env_locations);
+        dirs.extend(targets.watch_dirs());
+        if let Ok(mut shared) = self.env_targets.lock() {
+            *shared = targets;
+        }
+
+        let stale: Vec<PathBuf> = self.watched.difference(&dirs).cloned().collect();
+        for dir in stale {
+            // Fails harmlessly if the directory was already deleted
+            let _ = self.watcher.unwatch(&dir);
+            self.watched.remove(&dir);
+        }
+
+        for dir in dirs {
+            if self.watched.contains(&dir) {
+                continue;
+            }
+            match self.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.watched.insert(dir);
+                }
+                Err(e) => {
+                    tracing::debug!(dir = %dir.display(), error = ?e, "Failed to watch directory");
+                }
+            }
+        }
+
+        tracing::trace!(watched = self.watched.len(), "Venv watcher synced");
+    }
+}
+
+/// Directories to watch for documents at `file_paths`: every ancestor up to
+/// (and including) `git_toplevel`, plus existing venv dirs inside those ancestors.
+pub fn watch_dirs_for<'a>(
+    file_paths: impl Iterator<Item = &'a Path>,
+    git_toplevel: &Path,
+    venv_dirs: &[String],
+) -> HashSet<PathBuf> {
+    let mut dirs = HashSet::new();
+    for file_path in file_paths {
+        let mut current = file_path.parent();
+        while let Some(dir) = current {
+            if !dir.starts_with(git_toplevel) || !dirs.insert(dir.to_path_buf()) {
+                // Outside the toplevel, or ancestors already collected via another document
+                break;
+            }
+            for name in venv_dirs {
+                let venv = dir.join(name);
+                if venv.is_dir() {
+                    dirs.insert(venv);
+                }
+            }
+            current = dir.parent();
+        }
+    }
+    dirs
+}
+
+/// Directories to watch for package changes in a pooled venv: the venv itself
+/// (`pyvenv.cfg`), its parent (lockfiles), and its `lib/python*/site-packages`.
+pub fn package_watch_dirs(venv: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![venv.to_path_buf()];
+    dirs.extend(venv.parent().map(Path::to_path_buf));
+
+    if let Ok(entries) = std::fs::read_dir(venv.join("lib")) {
+        dirs.extend(
+            entries
+                .flatten()
+                .filter(|e| e.file_name().to_string_lossy().starts_with("python"))
+                .map(|e| e.path().join(SITE_PACKAGES))
+                .filter(|p| p.is_dir()),
+        );
+    }
+    dirs
+}
+
+/// Whether `path` is a venv directory or a venv marker such as `pyvenv.cfg` or
+/// `conda-meta` (venv created/removed)
+pub fn is_layout_path(path: &Path, venv_dirs: &[String], venv_markers: &[String]) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| {
+            name == CONDA_META
+                || venv_markers.iter().any(|m| m == name)
+                || venv_dirs.iter().any(|d| d == name)
+        })
+}
+
+/// Find the pooled venv whose installed packages are affected by a change at `path`
+pub fn package_change_venv<'a>(
+    path: &Path,
+    venvs: impl IntoIterator<Item = &'a PathBuf>,
+) -> Option<&'a PathBuf> {
+    let file_name = path.file_name().and_then(|n| n.to_str())?;
+    venvs.into_iter().find(|venv| {
+        if file_name == PYVENV_CFG {
+            path.parent() == Some(venv.as_path())
+        } else if LOCK_FILES.contains(&file_name) {
+            path.parent() == venv.parent()
+        } else {
+            is_package_path(path) && path.starts_with(venv)
+        }
+    })
+}
+
+fn is_package_path(path: &Path) -> bool {
+    // Bytecode caches are written by any Python run, not by installs
+    let in_site_packages = path
+        .parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|n| n == SITE_PACKAGES)
+        && path.file_name().is_some_and(|n| n != "__pycache__");
+    in_site_packages
+        || path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|name| LOCK_FILES.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_paths() {
+        let dirs = vec![".venv".to_string(), "venv".to_string()];
+        let markers = vec!["pyvenv.cfg".to_string(), ".tool-env".to_string()];
+        assert!(is_layout_path(Path::new("/p/.venv"), &dirs, &markers));
+        assert!(is_layout_path(Path::new("/p/venv"), &dirs, &markers));
+        assert!(is_layout_path(
+            Path::new("/p/.venv/pyvenv.cfg"),
+            &dirs,
+            &markers
+        ));
+        assert!(is_layout_path(
+            Path::new("/p/venv/.tool-env"),
+            &dirs,
+            &markers
+        ));
+        assert!(is_layout_path(
+            Path::new("/envs/ds/conda-meta"),
+            &dirs,
+            &markers
+        ));
+        assert!(!is_layout_path(Path::new("/p/main.py"), &dirs, &markers));
+    }
+
+    #[test]
+    fn package_changes_map_to_pooled_venv() {
+        let venvs = [PathBuf::from("/a/.venv"), PathBuf::from("/b/.venv")];
+        let venv_of = |p: &str| package_change_venv(Path::new(p), &venvs).cloned();
+
+        assert_eq!(
+            venv_of("/b/.venv/lib/python3.12/site-packages/requests"),
+            Some(venvs[1].clone())
+        );
+        assert_eq!(venv_of("/a/uv.lock"), Some(venvs[0].clone()));
+        assert_eq!(venv_of("/b/poetry.lock"), Some(venvs[1].clone()));
+        assert_eq!(venv_of("/a/.venv/pyvenv.cfg"), Some(venvs[0].clone()));
+        assert_eq!(venv_of("/c/uv.lock"), None);
+        assert_eq!(
+            venv_of("/a/.venv/lib/python3.12/site-packages/__pycache__"),
+            None
+        );
+        assert_eq!(
+            venv_of("/a/.venv/lib/python3.12/site-packages/x/y.py"),
+            None
+        );
+    }
+
+    #[test]
+    fn env_targets_match_envs_and_container_children() {
+        let temp = tempfile::tempdir().unwrap();
+        let cache = temp.path().join("virtualenvs");
+        let rule_target = temp.path().join("tools/envs/shared");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::create_dir_all(temp.path().join("tools/envs")).unwrap();
+
+        let targets = EnvTargets::new(
+            [
+                EnvLocation::Container(cache.clone()),
+                EnvLocation::Env(rule_target.clone()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert!(targets.is_env_path(&cache.join("app-AbCdEfGh-py3.12")));
+        assert!(targets.is_env_path(&rule_target));
+        assert!(!targets.is_env_path(&temp.path().join("tools/envs/other")));
+        assert!(!targets.is_env_path(&cache));
+
+        // The rule target does not exist yet: its parent is watched for its creation
+        let dirs: HashSet<PathBuf> = targets.watch_dirs().collect();
+        let expected: HashSet<PathBuf> = [cache, temp.path().join("tools/envs")]
+            .into_iter()
+            .collect();
+        assert_eq!(dirs, expected);
+    }
+
+    #[test]
+    fn watch_dirs_stop_at_toplevel() {
+        let temp = tempfile::tempdir().unwrap();
+        let top = temp.path();
+        std::fs::create_dir_all(top.join("a/b")).unwrap();
+        std::fs::create_dir_all(top.join("a/.venv")).unwrap();
+        let files = [top.join("a/b/x.py"), top.join("a/y.py")];
+
+        let dirs = watch_dirs_for(
+            files.iter().map(|p| p.as_path()),
+            top,
+            &[".venv".to_string()],
+        );
+
+        let expected: HashSet<PathBuf> = [
+            top.to_path_buf(),
+            top.join("a"),
+            top.join("a/b"),
+            top.join("a/.venv"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(dirs, expected);
+    }
+}